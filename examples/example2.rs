@@ -6,8 +6,6 @@ use pjsip_client::pjsua_config::PjsuaConfig;
 use pjsip_client::pjsua_softphone_api::PjsuaInstanceUninit;
 use pjsip_client::transport::PjsuaTransport;
 
-use pjsip_client::pjsua_memory_pool::PjsuaMemoryPool;
-
 use pjsip_client::pjmedia::pjmedia_port_audio_sink::{CustomSinkMediaPort, CustomSinkMediaPortRx};
 use pjsip_client::pjmedia::pjmedia_port_audio_stream::{
     CustomStreamMediaPort, CustomStreamMediaPortTx,
@@ -79,21 +77,17 @@ pub async fn read_pcm_and_send_task(
 }
 
 pub async fn handle_call(incoming_call: pjsua_call::PjsuaIncomingCall<'_>) {
-    let mem_pool = PjsuaMemoryPool::new(10000, 10000).expect("Failed to create memory pool");
-
     let call = incoming_call
         .answer_session_progress()
         .await
         .expect("answer failed!");
 
-    let (sink_media_port, frames_rx) =
-        CustomSinkMediaPort::new(8000, 1, 8000, &mem_pool).expect("test");
+    let (sink_media_port, frames_rx) = CustomSinkMediaPort::new(8000, 1, 8000).expect("test");
 
-    let (stream_media_port, frames_tx) =
-        CustomStreamMediaPort::new(8000, 1, 8000, &mem_pool).expect("test");
+    let (stream_media_port, frames_tx) = CustomStreamMediaPort::new(8000, 1, 8000).expect("test");
 
     let call = call
-        .add(sink_media_port, stream_media_port, &mem_pool)
+        .add(sink_media_port, stream_media_port)
         .await
         .expect("connect failed!");
 
@@ -128,7 +122,8 @@ async fn main() {
         .set_transport(transport)
         .expect("set_transport failed!");
 
-    let account_config1 = AccountConfig::new("7002", "7002", "127.0.0.1:5000");
+    let account_config1 =
+        AccountConfig::new("7002", "7002", "127.0.0.1:5000").expect("invalid account config!");
 
     let instance = instance.start().expect("start failed!");
 
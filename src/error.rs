@@ -1,25 +1,70 @@
 use std::fmt::Debug;
 
-#[derive(Debug, Clone)]
-pub struct PjsuaError {
-    pub code: pjsua::pj_status_t,
-    pub message: String,
+//pj_status_t ranges, see pj/errno.h, pjsip/sip_errno.h and pjmedia/errno.h.
+const PJ_ERRNO_START_STATUS: pjsua::pj_status_t = 70_000;
+const PJ_ERRNO_START_SYS: pjsua::pj_status_t = 120_000;
+const PJ_ERRNO_START_USER: pjsua::pj_status_t = 170_000;
+const PJ_ERRNO_SPACE_SIZE: pjsua::pj_status_t = 50_000;
+
+const PJSIP_ERRNO_START: pjsua::pj_status_t = PJ_ERRNO_START_USER;
+const PJLIB_UTIL_ERRNO_START: pjsua::pj_status_t = PJ_ERRNO_START_USER + PJ_ERRNO_SPACE_SIZE * 3;
+const PJMEDIA_ERRNO_START: pjsua::pj_status_t = PJ_ERRNO_START_USER + PJ_ERRNO_SPACE_SIZE;
+const PJNATH_ERRNO_START: pjsua::pj_status_t = PJ_ERRNO_START_USER + PJ_ERRNO_SPACE_SIZE * 4;
+const PJMEDIA_AUDIODEV_ERRNO_START: pjsua::pj_status_t =
+    PJ_ERRNO_START_USER + PJ_ERRNO_SPACE_SIZE * 5;
+
+//returned to pjmedia by port callbacks, see pj/errno.h.
+pub(crate) const PJ_EINVAL: pjsua::pj_status_t = PJ_ERRNO_START_STATUS + 4;
+pub(crate) const PJ_EBUG: pjsua::pj_status_t = PJ_ERRNO_START_STATUS + 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SipStatusClass {
+    Provisional,
+    Success,
+    Redirection,
+    ClientFailure,
+    ServerFailure,
+    GlobalFailure,
 }
 
-impl std::fmt::Display for PjsuaError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "PjsuaError: code: {}, message: {}",
-            self.code, self.message
-        )
+impl SipStatusClass {
+    fn from_status_code(status_code: u16) -> Option<Self> {
+        match status_code {
+            100..=199 => Some(SipStatusClass::Provisional),
+            200..=299 => Some(SipStatusClass::Success),
+            300..=399 => Some(SipStatusClass::Redirection),
+            400..=499 => Some(SipStatusClass::ClientFailure),
+            500..=599 => Some(SipStatusClass::ServerFailure),
+            600..=699 => Some(SipStatusClass::GlobalFailure),
+            _ => None,
+        }
     }
 }
 
-impl std::error::Error for PjsuaError {}
+//which library a pj_status_t belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusFamily {
+    //operating system errno, e.g. ECONNREFUSED.
+    Errno(i32),
+    Pjlib,
+    //a SIP response status mapped to a pj_status_t, e.g. 408 for a timed out transaction.
+    Sip(SipStatusClass, u16),
+    Pjsip,
+    PjlibUtil,
+    Pjnath,
+    Pjmedia,
+    PjmediaAudioDev,
+    Other,
+}
 
-impl From<pjsua::pj_status_t> for PjsuaError {
-    fn from(code: pjsua::pj_status_t) -> Self {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PjStatus {
+    pub code: pjsua::pj_status_t,
+    pub message: String,
+}
+
+impl PjStatus {
+    pub fn new(code: pjsua::pj_status_t) -> Self {
         let message = unsafe {
             let mut buffer = [0u8; 256];
             let ptr = buffer.as_mut_ptr() as *mut i8;
@@ -32,23 +77,148 @@ impl From<pjsua::pj_status_t> for PjsuaError {
             str
         };
 
-        PjsuaError { code, message }
+        PjStatus { code, message }
+    }
+
+    pub fn family(&self) -> StatusFamily {
+        let code = self.code;
+
+        let in_space =
+            |start: pjsua::pj_status_t| (start..start + PJ_ERRNO_SPACE_SIZE).contains(&code);
+
+        if in_space(PJ_ERRNO_START_STATUS) {
+            StatusFamily::Pjlib
+        } else if in_space(PJ_ERRNO_START_SYS) {
+            StatusFamily::Errno(code - PJ_ERRNO_START_SYS)
+        } else if in_space(PJSIP_ERRNO_START) {
+            let status_code = (code - PJSIP_ERRNO_START) as u16;
+
+            match SipStatusClass::from_status_code(status_code) {
+                Some(class) => StatusFamily::Sip(class, status_code),
+                None => StatusFamily::Pjsip,
+            }
+        } else if in_space(PJMEDIA_ERRNO_START) {
+            StatusFamily::Pjmedia
+        } else if in_space(PJLIB_UTIL_ERRNO_START) {
+            StatusFamily::PjlibUtil
+        } else if in_space(PJNATH_ERRNO_START) {
+            StatusFamily::Pjnath
+        } else if in_space(PJMEDIA_AUDIODEV_ERRNO_START) {
+            StatusFamily::PjmediaAudioDev
+        } else {
+            StatusFamily::Other
+        }
     }
 }
 
-pub fn get_error_as_option(code: pjsua::pj_status_t) -> Option<PjsuaError> {
-    const PJSUA_SUCCESS: i32 = pjsua::pj_constants__PJ_SUCCESS as i32;
-    match code {
-        PJSUA_SUCCESS => None,
-        _ => Some(PjsuaError::from(code)),
+//every variant carries the operation that failed, usually the name of the pjsua function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PjsuaError {
+    //a pjlib, pjsip or pjmedia function returned an error status.
+    Status {
+        operation: &'static str,
+        status: PjStatus,
+    },
+    InvalidState {
+        operation: &'static str,
+        expected: String,
+        actual: String,
+    },
+    InvalidConfig {
+        operation: &'static str,
+        reason: String,
+    },
+    //pjsua instance creation and teardown, and resources that could not be allocated.
+    Lifecycle {
+        operation: &'static str,
+        reason: &'static str,
+    },
+    ChannelClosed {
+        operation: &'static str,
+    },
+    //an extern "C" callback panicked, operation is the name of the callback.
+    CallbackPanicked {
+        operation: &'static str,
+        message: String,
+    },
+}
+
+impl PjsuaError {
+    pub fn operation(&self) -> &'static str {
+        match self {
+            PjsuaError::Status { operation, .. }
+            | PjsuaError::InvalidState { operation, .. }
+            | PjsuaError::InvalidConfig { operation, .. }
+            | PjsuaError::Lifecycle { operation, .. }
+            | PjsuaError::ChannelClosed { operation }
+            | PjsuaError::CallbackPanicked { operation, .. } => operation,
+        }
+    }
+
+    pub fn status(&self) -> Option<&PjStatus> {
+        match self {
+            PjsuaError::Status { status, .. } => Some(status),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn invalid_config(operation: &'static str, reason: impl Into<String>) -> Self {
+        PjsuaError::InvalidConfig {
+            operation,
+            reason: reason.into(),
+        }
     }
 }
 
-pub fn get_error_as_result(code: pjsua::pj_status_t) -> Result<(), PjsuaError> {
+impl std::fmt::Display for PjsuaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PjsuaError::Status { operation, status } => write!(
+                f,
+                "{} failed with status {}: {}",
+                operation, status.code, status.message
+            ),
+            PjsuaError::InvalidState {
+                operation,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: expected state {}, got {}",
+                operation, expected, actual
+            ),
+            PjsuaError::InvalidConfig { operation, reason } => {
+                write!(f, "{}: invalid config: {}", operation, reason)
+            }
+            PjsuaError::Lifecycle { operation, reason } => write!(f, "{}: {}", operation, reason),
+            PjsuaError::ChannelClosed { operation } => write!(f, "{}: channel closed", operation),
+            PjsuaError::CallbackPanicked { operation, message } => {
+                write!(f, "{} panicked: {}", operation, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PjsuaError {}
+
+pub fn get_error_as_option(
+    operation: &'static str,
+    code: pjsua::pj_status_t,
+) -> Option<PjsuaError> {
+    get_error_as_result(operation, code).err()
+}
+
+pub fn get_error_as_result(
+    operation: &'static str,
+    code: pjsua::pj_status_t,
+) -> Result<(), PjsuaError> {
     const PJSUA_SUCCESS: i32 = pjsua::pj_constants__PJ_SUCCESS as i32;
     match code {
         PJSUA_SUCCESS => Ok(()),
-        _ => Err(PjsuaError::from(code)),
+        _ => Err(PjsuaError::Status {
+            operation,
+            status: PjStatus::new(code),
+        }),
     }
 }
 
@@ -59,40 +229,56 @@ pub fn map_option_to_result<T>(error: Option<PjsuaError>) -> Result<(), PjsuaErr
     }
 }
 
+//extern "C" callbacks must not unwind into pjsip. A panic is logged and returned as
+//PjsuaError::CallbackPanicked, the callback then hands a safe default back to pjsip.
+pub(crate) fn catch_callback_panic<R>(
+    callback: &'static str,
+    f: impl FnOnce() -> R,
+) -> Result<R, PjsuaError> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|payload| {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => match payload.downcast_ref::<String>() {
+                Some(message) => message.clone(),
+                None => "unknown panic payload".to_string(),
+            },
+        };
+
+        tracing::error!(callback, %message, "callback panicked");
+
+        PjsuaError::CallbackPanicked {
+            operation: callback,
+            message,
+        }
+    })
+}
+
+//panics instead of exiting the process, inside of callbacks the panic is caught by
+//catch_callback_panic.
 #[macro_export]
 macro_rules! ffi_assert {
     ($cond:expr) => {
         if !$cond {
-            let backtrace = std::backtrace::Backtrace::capture();
-            eprintln!("Assertion failed: {}", stringify!($cond));
-            eprintln!("{:?}", backtrace);
-            std::process::exit(1);
+            panic!("assertion failed: {}", stringify!($cond));
         }
     };
     ($cond:expr, $($arg:tt)*) => {
         if !$cond {
-            let backtrace = std::backtrace::Backtrace::capture();
-            eprintln!("Assertion failed: {}", stringify!($cond));
-            eprintln!("{:?}", backtrace);
-            eprintln!($($arg)*);
-
-            std::process::exit(1);
+            panic!("assertion failed: {}: {}", stringify!($cond), format_args!($($arg)*));
         }
     };
 }
 
 pub fn ffi_assert_res<T, E: Debug>(res: Result<T, E>) -> T {
-    unsafe {
-        ffi_assert!(res.is_ok(), "{:?}", res.unwrap_err_unchecked());
-
-        res.unwrap_unchecked()
+    match res {
+        Ok(value) => value,
+        Err(error) => panic!("assertion failed: {:?}", error),
     }
 }
 
 pub fn ffi_assert_option<T: Debug>(res: Option<T>) -> T {
-    unsafe {
-        ffi_assert!(res.is_some(), "{:?}", res);
-
-        res.unwrap_unchecked()
+    match res {
+        Some(value) => value,
+        None => panic!("assertion failed: value is None"),
     }
 }
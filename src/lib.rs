@@ -6,9 +6,18 @@ pub mod error;
 pub mod pj_types;
 pub mod pjmedia;
 pub mod pjsua_account_config;
+pub mod pjsua_blocking;
 pub mod pjsua_call;
+pub mod pjsua_call_filter;
+pub mod pjsua_caller_id;
 pub mod pjsua_config;
+pub mod pjsua_config_file;
+pub mod pjsua_ice;
+pub mod pjsua_log;
+pub mod pjsua_master_port;
 pub mod pjsua_memory_pool;
+pub mod pjsua_nat;
 pub mod pjsua_softphone_api;
+pub mod pjsua_srtp;
 pub mod tokio_utils;
 pub mod transport;
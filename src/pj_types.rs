@@ -1,3 +1,4 @@
+use super::error::PjsuaError;
 use super::pjsua_memory_pool::{PjsuaMemoryPool, PoolBuffer};
 
 pub struct PjString<'a> {
@@ -6,21 +7,25 @@ pub struct PjString<'a> {
 }
 
 impl<'a> PjString<'a> {
-    pub fn alloc(string: impl AsRef<str>, mem_pool: &'a PjsuaMemoryPool) -> PjString<'a> {
+    //the pool buffer is NUL terminated, slen excludes the terminator like pj_str does.
+    pub fn alloc(
+        string: impl AsRef<str>,
+        mem_pool: &'a PjsuaMemoryPool,
+    ) -> Result<PjString<'a>, PjsuaError> {
         let string = string.as_ref();
-        let mut pool_buffer = mem_pool.alloc::<u8>(string.len() + 1);
+        let mut pool_buffer = mem_pool.alloc::<u8>(string.len() + 1)?;
+
+        pool_buffer[..string.len()].copy_from_slice(string.as_bytes());
 
         let pj_str = pjsua::pj_str_t {
-            ptr: pool_buffer.as_mut() as *mut _ as *mut std::os::raw::c_char,
-            slen: pool_buffer.len() as pjsua::pj_ssize_t,
+            ptr: pool_buffer.as_mut_ptr() as *mut std::os::raw::c_char,
+            slen: string.len() as pjsua::pj_ssize_t,
         };
 
-        unsafe { std::ptr::copy(string.as_ptr(), pj_str.ptr as *mut u8, string.len()) };
-
-        PjString {
+        Ok(PjString {
             pj_str,
             _pool_buffer: pool_buffer,
-        }
+        })
     }
 }
 
@@ -36,4 +41,15 @@ impl<'a> AsMut<pjsua::pj_str_t> for PjString<'a> {
     }
 }
 
+pub(crate) fn pj_str_to_string(pj_str: &pjsua::pj_str_t) -> String {
+    if pj_str.ptr.is_null() || pj_str.slen <= 0 {
+        return String::new();
+    }
+
+    let bytes =
+        unsafe { std::slice::from_raw_parts(pj_str.ptr as *const u8, pj_str.slen as usize) };
+
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
 pub use super::pjmedia::pjmedia_api::Frame;
@@ -1,12 +1,14 @@
 use crate::error::get_error_as_result;
 use crate::error::PjsuaError;
+use crate::pjsua_memory_pool::PjsuaMemoryPool;
+use crate::tokio_utils::run_on_pjsua_thread_blocking;
 use std::sync::atomic::AtomicU32;
 
 use tokio::sync::mpsc as tokio_mpsc;
 
 pub(super) const BITS_PER_SAMPLE: usize = 16;
 
-pub(super) fn perform_pjmedia_format_checks_zero_division(
+pub(crate) fn perform_pjmedia_format_checks_zero_division(
     samples_per_frame: usize,
     audio_format_detail: &pjsua::pjmedia_audio_format_detail,
 ) -> Result<(), PjsuaError> {
@@ -19,7 +21,10 @@ pub(super) fn perform_pjmedia_format_checks_zero_division(
                               Division by zero happens at conference.c:387 \
                                           if (conf_ptime % port_ptime)") ;
 
-        return Err(PjsuaError { code: -1, message });
+        return Err(PjsuaError::InvalidConfig {
+            operation: "pjmedia_format_checks",
+            reason: message,
+        });
     }
 
     Ok(())
@@ -49,19 +54,25 @@ pub unsafe fn port_info(
     //https://github.com/chakrit/pjsip/blob/b0af6c8fc8ed97bb03d3afa4ab42c24f46a9212b/pjmedia/src/pjmedia/port.c#L33
     //https://github.com/pjsip/pjproject/blob/01d37bf15a9121e6e78afe41a5c3ef4fa2ae3308/pjsip-apps/src/samples/playsine.c#L140C5-L140C27
     unsafe {
-        let status = get_error_as_result(pjsua::pjmedia_port_info_init2(
-            &mut port_info,
-            name,
-            signature,
-            pjsua::pjmedia_dir_PJMEDIA_DIR_ENCODING_DECODING,
-            format,
-        ));
+        let status = get_error_as_result(
+            "pjmedia_port_info_init2",
+            pjsua::pjmedia_port_info_init2(
+                &mut port_info,
+                name,
+                signature,
+                pjsua::pjmedia_dir_PJMEDIA_DIR_ENCODING_DECODING,
+                format,
+            ),
+        );
 
         status?;
     }
 
-    eprintln!("fmt.type: {}", port_info.fmt.type_);
-    eprintln!("fmt.type_detail: {}", port_info.fmt.detail_type);
+    tracing::trace!(
+        fmt_type = port_info.fmt.type_,
+        fmt_detail_type = port_info.fmt.detail_type,
+        "port info initialized"
+    );
 
     Ok(port_info)
 }
@@ -115,10 +126,10 @@ impl Frame {
         channels_count: usize,
     ) -> Result<Self, PjsuaError> {
         if raw_frame.size % raw_frame.size != 0 {
-            Err(PjsuaError {
-                code: -1,
-                message: "frame buffer is not even cannot construct i16 array".to_string(),
-            })?;
+            Err(PjsuaError::invalid_config(
+                "from_raw_frame",
+                "frame buffer is not even cannot construct i16 array",
+            ))?;
         }
 
         let frame_data: &[u8] =
@@ -178,3 +189,73 @@ impl std::fmt::Display for SendError {
         }
     }
 }
+
+//a media port in the conference bridge. Leaves the bridge and is destroyed on the pjsua worker
+//when dropped.
+pub(crate) struct ConfBridgePort {
+    base: Box<pjsua::pjmedia_port>,
+    _name: std::ffi::CString,
+    port_slot: pjsua::pjsua_conf_port_id,
+    kind: &'static str,
+}
+
+impl ConfBridgePort {
+    //the conference bridge allocates from mem_pool, it has to outlive the returned port.
+    pub(super) fn add(
+        mut base: Box<pjsua::pjmedia_port>,
+        name: std::ffi::CString,
+        mem_pool: &PjsuaMemoryPool,
+        kind: &'static str,
+    ) -> Result<Self, PjsuaError> {
+        let mut port_slot = pjsua::pjsua_conf_port_id::default();
+
+        unsafe {
+            let status = run_on_pjsua_thread_blocking(|| {
+                pjsua::pjsua_conf_add_port(mem_pool.raw_handle(), base.as_mut(), &mut port_slot)
+            })?;
+            get_error_as_result("pjsua_conf_add_port", status)?;
+            tracing::debug!(port_slot, kind, "added port to conf bridge");
+        }
+
+        Ok(ConfBridgePort {
+            base,
+            _name: name,
+            port_slot,
+            kind,
+        })
+    }
+
+    pub(crate) fn port_slot(&self) -> pjsua::pjsua_conf_port_id {
+        self.port_slot
+    }
+}
+
+impl Drop for ConfBridgePort {
+    fn drop(&mut self) {
+        tracing::debug!(
+            port_slot = self.port_slot,
+            kind = self.kind,
+            "removing port from conf bridge"
+        );
+
+        let port_slot = self.port_slot;
+        let base = self.base.as_mut();
+
+        let released = unsafe {
+            run_on_pjsua_thread_blocking(|| {
+                let removed = get_error_as_result(
+                    "pjsua_conf_remove_port",
+                    pjsua::pjsua_conf_remove_port(port_slot),
+                );
+                let destroyed =
+                    get_error_as_result("pjmedia_port_destroy", pjsua::pjmedia_port_destroy(base));
+
+                removed.and(destroyed)
+            })
+        };
+
+        if let Err(e) = released.and_then(|released| released) {
+            tracing::warn!(port_slot, kind = self.kind, error = %e, "failed to release media port");
+        }
+    }
+}
@@ -1,9 +1,9 @@
-use crate::error::ffi_assert_res;
-use crate::error::get_error_as_result;
+use crate::error::catch_callback_panic;
 use crate::error::PjsuaError;
+use crate::error::{PJ_EBUG, PJ_EINVAL};
 use crate::pjsua_memory_pool::PjsuaMemoryPool;
 
-use crate::pj_types::PjString;
+use std::ffi::CString;
 
 use crate::pj_types::Frame;
 
@@ -11,9 +11,8 @@ use std::sync::atomic::AtomicU32;
 
 use tokio::sync::mpsc as tokio_mpsc;
 
-use crate::ffi_assert;
-
 use super::pjmedia_api;
+use super::pjmedia_api::ConfBridgePort;
 
 unsafe extern "C" fn custom_port_put_frame(
     port: *mut pjsua::pjmedia_port,
@@ -21,57 +20,71 @@ unsafe extern "C" fn custom_port_put_frame(
 ) -> pjsua::pj_status_t {
     static mut COUNTER: AtomicU32 = AtomicU32::new(0);
 
-    let count = unsafe { COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst) };
+    catch_callback_panic("custom_port_put_frame", || {
+        if frame.is_null() || (*frame).buf.is_null() || (*frame).size == 0 {
+            return 0;
+        }
 
-    if count % 100 == 0 {
-        println!(
-            "custom_port_put_frame: frame buffer size: {:?}",
-            (*frame).size
-        );
-    }
+        let count = unsafe { COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst) };
 
-    if frame.is_null() || (*frame).buf.is_null() || (*frame).size == 0 {
-        return 0;
-    }
+        if count % 100 == 0 {
+            tracing::trace!(frame_size = (*frame).size, "custom_port_put_frame");
+        }
 
-    let media_port_data = unsafe { (*port).port_data.pdata as *mut MediaPortData };
-    let sample_rate = (*media_port_data).sample_rate;
-    let channels_count = (*media_port_data).channels_count;
+        let media_port_data = unsafe { (*port).port_data.pdata as *mut MediaPortData };
+        let sample_rate = (*media_port_data).sample_rate;
+        let channels_count = (*media_port_data).channels_count;
 
-    let frame_type = unsafe { (*frame).type_ };
-    let _bit_info = unsafe { (*frame).bit_info };
+        let frame_type = unsafe { (*frame).type_ };
+        let _bit_info = unsafe { (*frame).bit_info };
 
-    ffi_assert!(frame_type == pjsua::pjmedia_frame_type_PJMEDIA_FRAME_TYPE_AUDIO);
+        if frame_type != pjsua::pjmedia_frame_type_PJMEDIA_FRAME_TYPE_AUDIO {
+            tracing::trace!(frame_type, "skipping non audio frame");
+            return 0;
+        }
 
-    let frame =
-        ffi_assert_res(unsafe { Frame::from_raw_frame(&*frame, sample_rate, channels_count) });
+        let frame = match unsafe { Frame::from_raw_frame(&*frame, sample_rate, channels_count) } {
+            Ok(frame) => frame,
+            Err(e) => {
+                tracing::warn!(error = ?e, "dropping malformed frame");
+                return PJ_EINVAL;
+            }
+        };
 
-    if let Err(_) = (*media_port_data).frames_tx.try_send(frame) {
-        eprintln!("Buffer full, dropping frame...");
-    }
+        if let Err(_) = (*media_port_data).frames_tx.try_send(frame) {
+            tracing::warn!("sink buffer full, dropping frame");
+        }
 
-    return 0; // or appropriate status
+        return 0; // or appropriate status
+    })
+    .unwrap_or(PJ_EBUG)
 }
 
 unsafe extern "C" fn custom_port_get_frame(
     _port: *mut pjsua::pjmedia_port,
     frame: *mut pjsua::pjmedia_frame,
 ) -> pjsua::pj_status_t {
-    println!(
-        "custom_port_get_frame: frame buffer size: {:?}",
-        (*frame).size
-    );
+    catch_callback_panic("custom_port_get_frame", || {
+        if let Some(frame) = frame.as_ref() {
+            tracing::trace!(frame_size = frame.size, "custom_port_get_frame");
+        }
 
-    return 0; // or appropriate status
+        return 0; // or appropriate status
+    })
+    .unwrap_or(PJ_EBUG)
 }
 
 unsafe extern "C" fn custom_port_on_destroy(port: *mut pjsua::pjmedia_port) -> pjsua::pj_status_t {
     //base.port_data.pdata
 
-    let _port: Box<MediaPortData> = Box::from_raw((*port).port_data.pdata as *mut MediaPortData);
+    catch_callback_panic("custom_port_on_destroy", || {
+        let _port: Box<MediaPortData> =
+            Box::from_raw((*port).port_data.pdata as *mut MediaPortData);
 
-    eprintln!("custom_port_on_destroy");
-    return 0; // or appropriate status
+        tracing::trace!("custom_port_on_destroy");
+        return 0; // or appropriate status
+    })
+    .unwrap_or(PJ_EBUG)
 }
 
 struct MediaPortData {
@@ -80,10 +93,11 @@ struct MediaPortData {
     channels_count: usize,
 }
 
-pub struct CustomSinkMediaPort<'a> {
+pub struct CustomSinkMediaPort {
     base: Box<pjsua::pjmedia_port>,
     _format: Box<pjsua::pjmedia_format>,
-    _name: PjString<'a>,
+    //the port info keeps a pj_str_t pointing into this buffer.
+    name: CString,
 }
 
 pub struct CustomSinkMediaPortRx {
@@ -100,16 +114,17 @@ use crate::pjsua_softphone_api::PjsuaInstanceStarted;
 
 use super::next_num;
 
-impl<'a> CustomSinkMediaPort<'a> {
+impl CustomSinkMediaPort {
     pub fn new(
         sample_rate: u32,
         channels_count: usize,
         samples_per_frame: usize,
-        mem_pool: &'a PjsuaMemoryPool,
     ) -> Result<(Self, CustomSinkMediaPortRx), PjsuaError> {
         let mut base: Box<pjsua::pjmedia_port> = Box::new(unsafe { std::mem::zeroed() });
 
-        let name = PjString::alloc(format!("CustomSinkMediaPort_{}", next_num()), &mem_pool);
+        let name = CString::new(format!("CustomSinkMediaPort_{}", next_num()))
+            .expect("port name has no interior NUL");
+        let pj_name = unsafe { pjsua::pj_str(name.as_ptr() as *mut i8) };
 
         let format = Box::new(pjmedia_api::port_format(
             sample_rate,
@@ -117,7 +132,7 @@ impl<'a> CustomSinkMediaPort<'a> {
             samples_per_frame as _,
         )?);
 
-        let port_info = unsafe { pjmedia_api::port_info(format.as_ref(), name.as_ref()) };
+        let port_info = unsafe { pjmedia_api::port_info(format.as_ref(), &pj_name) };
 
         base.put_frame = Some(custom_port_put_frame);
         base.get_frame = Some(custom_port_get_frame);
@@ -137,68 +152,37 @@ impl<'a> CustomSinkMediaPort<'a> {
         Ok((
             CustomSinkMediaPort {
                 base,
-                _name: name,
+                name,
                 _format: format,
             },
             CustomSinkMediaPortRx { frames_rx },
         ))
     }
 
-    pub(crate) fn add(
+    //the conference bridge allocates from mem_pool, it has to outlive the returned port.
+    pub(crate) fn add_to_conf_bridge(
         self,
-        mem_pool: &'a PjsuaMemoryPool,
-        instance_started: &'a PjsuaInstanceStarted,
-    ) -> Result<CustomSinkMediaPortAdded<'a>, PjsuaError> {
-        CustomSinkMediaPortAdded::new(self, mem_pool, instance_started)
+        mem_pool: &PjsuaMemoryPool,
+    ) -> Result<ConfBridgePort, PjsuaError> {
+        ConfBridgePort::add(self.base, self.name, mem_pool, "sink")
     }
 }
 
 pub struct CustomSinkMediaPortAdded<'a> {
-    base: Box<pjsua::pjmedia_port>,
+    port: ConfBridgePort,
     _pjsua_instance: &'a PjsuaInstanceStarted,
-    port_slot: pjsua::pjsua_conf_port_id,
 }
 
 impl<'a> CustomSinkMediaPortAdded<'a> {
-    pub(crate) fn new(
-        media_port: CustomSinkMediaPort<'a>,
-        mem_pool: &'a PjsuaMemoryPool,
-        pjsua_instance: &'a PjsuaInstanceStarted,
-    ) -> Result<Self, PjsuaError> {
-        let mut base = media_port.base;
-        let mut port_slot = pjsua::pjsua_conf_port_id::default();
-
-        unsafe {
-            let status =
-                pjsua::pjsua_conf_add_port(mem_pool.raw_handle(), base.as_mut(), &mut port_slot);
-            get_error_as_result(status)?;
-            eprintln!("added port to conf bridge: {:?}", port_slot);
-        }
-
-        base.put_frame = Some(custom_port_put_frame);
-
-        Ok(CustomSinkMediaPortAdded {
-            base,
+    pub(crate) fn new(port: ConfBridgePort, pjsua_instance: &'a PjsuaInstanceStarted) -> Self {
+        CustomSinkMediaPortAdded {
+            port,
             _pjsua_instance: pjsua_instance,
-            port_slot,
-        })
+        }
     }
 
     pub fn port_slot(&self) -> i32 {
-        self.port_slot
-    }
-}
-
-impl<'a> Drop for CustomSinkMediaPortAdded<'a> {
-    fn drop(&mut self) {
-        unsafe {
-            eprintln!("removing port from conf bridge: {:?}", self.port_slot);
-            let status = pjsua::pjsua_conf_remove_port(self.port_slot);
-            get_error_as_result(status).unwrap();
-        }
-
-        let status = unsafe { pjsua::pjmedia_port_destroy(self.base.as_mut()) };
-        get_error_as_result(status).unwrap();
+        self.port.port_slot()
     }
 }
 
@@ -1,8 +1,7 @@
-use crate::error::get_error_as_result;
 use crate::error::PjsuaError;
 use crate::pjsua_memory_pool::PjsuaMemoryPool;
 
-use crate::pj_types::PjString;
+use std::ffi::CString;
 
 use crate::pj_types::Frame;
 
@@ -11,12 +10,11 @@ use futures::Sink;
 use futures::SinkExt;
 
 use super::pjmedia_api;
+use super::pjmedia_api::ConfBridgePort;
 
 use crate::pjsua_softphone_api::PjsuaInstanceStarted;
 
-use std::panic::catch_unwind;
-
-use crate::error::ffi_assert_res;
+use crate::error::{catch_callback_panic, PJ_EBUG};
 
 use super::next_num;
 
@@ -26,7 +24,7 @@ unsafe extern "C" fn custom_port_get_frame(
     port: *mut pjsua::pjmedia_port,
     tx_frame: *mut pjsua::pjmedia_frame,
 ) -> pjsua::pj_status_t {
-    let res = catch_unwind(|| {
+    let res = catch_callback_panic("custom_port_get_frame", || {
         let media_port_data = unsafe { (*port).port_data.pdata as *mut MediaPortData };
         let _sample_rate = (*media_port_data).sample_rate;
         let _channels_count = (*media_port_data).channels_count;
@@ -38,17 +36,11 @@ unsafe extern "C" fn custom_port_get_frame(
         if let pjsua::pjmedia_frame_type_PJMEDIA_FRAME_TYPE_AUDIO = frame_type {
             match (*media_port_data).frames_rx.try_next() {
                 Ok(Some(rx_frame)) => {
+                    //tx_frame.size is in bytes.
                     let tx_frame_data: &mut [u8] =
-                        std::slice::from_raw_parts_mut(tx_frame.buf as *mut _, tx_frame.size * 2);
-
-                    let rx_frame_bytes = rx_frame
-                        .data
-                        .into_iter()
-                        .map(|x| x.to_le_bytes())
-                        .flatten()
-                        .collect::<Vec<_>>();
+                        std::slice::from_raw_parts_mut(tx_frame.buf as *mut _, tx_frame.size);
 
-                    tx_frame_data.copy_from_slice(&rx_frame_bytes);
+                    write_samples(tx_frame_data, &rx_frame.data);
 
                     tx_frame.timestamp.u64_ = rx_frame.time.as_micros() as u64;
                 }
@@ -62,16 +54,35 @@ unsafe extern "C" fn custom_port_get_frame(
         }
     });
 
-    ffi_assert_res(res);
+    //the conference bridge treats a failed get_frame as silence.
+    match res {
+        Ok(()) => 0,
+        Err(_) => PJ_EBUG,
+    }
+}
 
-    return 0; // or appropriate status
+//a frame of the wrong size from the application is truncated or padded with silence, pjmedia's
+//buffer is never written past tx_frame.size.
+fn write_samples(tx_frame_data: &mut [u8], samples: &[i16]) {
+    let samples_bytes = samples
+        .iter()
+        .flat_map(|sample| sample.to_le_bytes())
+        .collect::<Vec<_>>();
+
+    let len = samples_bytes.len().min(tx_frame_data.len());
+    tx_frame_data[..len].copy_from_slice(&samples_bytes[..len]);
+    tx_frame_data[len..].fill(0);
 }
 
 unsafe extern "C" fn custom_port_on_destroy(port: *mut pjsua::pjmedia_port) -> pjsua::pj_status_t {
-    let _port: Box<MediaPortData> = Box::from_raw((*port).port_data.pdata as *mut MediaPortData);
-
-    eprintln!("custom_port_on_destroy");
-    return 0; // or appropriate status
+    catch_callback_panic("custom_port_on_destroy", || {
+        let _port: Box<MediaPortData> =
+            Box::from_raw((*port).port_data.pdata as *mut MediaPortData);
+
+        tracing::trace!("custom_port_on_destroy");
+        return 0; // or appropriate status
+    })
+    .unwrap_or(PJ_EBUG)
 }
 
 struct MediaPortData {
@@ -81,10 +92,11 @@ struct MediaPortData {
     channels_count: usize,
 }
 
-pub struct CustomStreamMediaPort<'a> {
+pub struct CustomStreamMediaPort {
     base: Box<pjsua::pjmedia_port>,
     _format: Box<pjsua::pjmedia_format>,
-    _name: PjString<'a>,
+    //the port info keeps a pj_str_t pointing into this buffer.
+    name: CString,
 }
 
 pub struct CustomStreamMediaPortTx {
@@ -131,16 +143,17 @@ impl CustomStreamMediaPortTx {
     }
 }
 
-impl<'a> CustomStreamMediaPort<'a> {
+impl CustomStreamMediaPort {
     pub fn new(
         sample_rate: u32,
         channels_count: usize,
         samples_per_frame: usize,
-        mem_pool: &'a PjsuaMemoryPool,
     ) -> Result<(Self, CustomStreamMediaPortTx), PjsuaError> {
         let mut base: Box<pjsua::pjmedia_port> = Box::new(unsafe { std::mem::zeroed() });
 
-        let name = PjString::alloc(format!("CustomStreamMediaPort_{}", next_num()), &mem_pool);
+        let name = CString::new(format!("CustomStreamMediaPort_{}", next_num()))
+            .expect("port name has no interior NUL");
+        let pj_name = unsafe { pjsua::pj_str(name.as_ptr() as *mut i8) };
 
         let format = Box::new(pjmedia_api::port_format(
             sample_rate,
@@ -148,7 +161,7 @@ impl<'a> CustomStreamMediaPort<'a> {
             samples_per_frame,
         )?);
 
-        let port_info = unsafe { pjmedia_api::port_info(format.as_ref(), name.as_ref()) };
+        let port_info = unsafe { pjmedia_api::port_info(format.as_ref(), &pj_name) };
 
         base.get_frame = Some(custom_port_get_frame);
 
@@ -167,7 +180,7 @@ impl<'a> CustomStreamMediaPort<'a> {
         Ok((
             CustomStreamMediaPort {
                 base,
-                _name: name,
+                name,
                 _format: format,
             },
             CustomStreamMediaPortTx {
@@ -178,61 +191,32 @@ impl<'a> CustomStreamMediaPort<'a> {
         ))
     }
 
-    pub(crate) fn add(
-        self,
-        mem_pool: &'a PjsuaMemoryPool,
-        instance_started: &'a PjsuaInstanceStarted,
-    ) -> Result<CustomStreamMediaPortAdded<'a>, PjsuaError> {
-        CustomStreamMediaPortAdded::new(self, mem_pool, instance_started)
+    //the conference bridge allocates from mem_pool, it has to outlive the returned port.
+    pub(crate) fn add_to_conf_bridge(
+        mut self,
+        mem_pool: &PjsuaMemoryPool,
+    ) -> Result<ConfBridgePort, PjsuaError> {
+        self.base.put_frame = Some(custom_port_get_frame);
+
+        ConfBridgePort::add(self.base, self.name, mem_pool, "stream")
     }
 }
 
 pub struct CustomStreamMediaPortAdded<'a> {
-    base: Box<pjsua::pjmedia_port>,
+    port: ConfBridgePort,
     _pjsua_instance: &'a PjsuaInstanceStarted,
-    port_slot: pjsua::pjsua_conf_port_id,
 }
 
 impl<'a> CustomStreamMediaPortAdded<'a> {
-    pub(crate) fn new(
-        media_port: CustomStreamMediaPort<'a>,
-        mem_pool: &'a PjsuaMemoryPool,
-        pjsua_instance: &'a PjsuaInstanceStarted,
-    ) -> Result<Self, PjsuaError> {
-        let mut base = media_port.base;
-        let mut port_slot = pjsua::pjsua_conf_port_id::default();
-
-        unsafe {
-            let status =
-                pjsua::pjsua_conf_add_port(mem_pool.raw_handle(), base.as_mut(), &mut port_slot);
-            get_error_as_result(status)?;
-            eprintln!("added port to conf bridge: {:?}", port_slot);
-        }
-
-        base.put_frame = Some(custom_port_get_frame);
-
-        Ok(CustomStreamMediaPortAdded {
-            base,
+    pub(crate) fn new(port: ConfBridgePort, pjsua_instance: &'a PjsuaInstanceStarted) -> Self {
+        CustomStreamMediaPortAdded {
+            port,
             _pjsua_instance: pjsua_instance,
-            port_slot,
-        })
+        }
     }
 
     pub fn port_slot(&self) -> i32 {
-        self.port_slot
-    }
-}
-
-impl<'a> Drop for CustomStreamMediaPortAdded<'a> {
-    fn drop(&mut self) {
-        unsafe {
-            eprintln!("removing port from conf bridge: {:?}", self.port_slot);
-            let status = pjsua::pjsua_conf_remove_port(self.port_slot);
-            get_error_as_result(status).unwrap();
-        }
-
-        let status = unsafe { pjsua::pjmedia_port_destroy(self.base.as_mut()) };
-        get_error_as_result(status).unwrap();
+        self.port.port_slot()
     }
 }
 
@@ -264,3 +248,68 @@ impl Sink<Frame> for CustomStreamMediaPortTx {
         self.frames_tx.poll_close_unpin(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUARD: u8 = 0xAB;
+
+    //calls get_frame for a frame of frame_size bytes in a buffer with guard bytes behind it.
+    fn get_frame(samples: &[i16], frame_size: usize) -> (pjsua::pj_status_t, Vec<u8>) {
+        let (mut frames_tx, frames_rx) = futures_mpsc::channel(1);
+        frames_tx
+            .try_send(Frame {
+                data: samples.into(),
+                time: std::time::Duration::ZERO,
+            })
+            .unwrap();
+
+        let mut port: pjsua::pjmedia_port = unsafe { std::mem::zeroed() };
+        port.port_data.pdata = Box::into_raw(Box::new(MediaPortData {
+            frames_rx,
+            sample_rate: 8000,
+            channels_count: 1,
+        })) as *mut _;
+
+        let mut buf = vec![GUARD; frame_size * 3];
+
+        let mut tx_frame: pjsua::pjmedia_frame = unsafe { std::mem::zeroed() };
+        tx_frame.type_ = pjsua::pjmedia_frame_type_PJMEDIA_FRAME_TYPE_AUDIO;
+        tx_frame.buf = buf.as_mut_ptr() as *mut _;
+        tx_frame.size = frame_size;
+
+        let status = unsafe { custom_port_get_frame(&mut port, &mut tx_frame) };
+
+        drop(unsafe { Box::from_raw(port.port_data.pdata as *mut MediaPortData) });
+
+        (status, buf)
+    }
+
+    #[test]
+    fn get_frame_stays_within_frame_size() {
+        let (status, buf) = get_frame(&[0x0102; 4], 8);
+
+        assert_eq!(status, 0);
+        assert_eq!(&buf[..8], &[0x02u8, 0x01].repeat(4)[..]);
+        assert!(buf[8..].iter().all(|&byte| byte == GUARD));
+    }
+
+    #[test]
+    fn get_frame_truncates_long_frames() {
+        let (status, buf) = get_frame(&[0x0102; 16], 8);
+
+        assert_eq!(status, 0);
+        assert_eq!(&buf[..8], &[0x02u8, 0x01].repeat(4)[..]);
+        assert!(buf[8..].iter().all(|&byte| byte == GUARD));
+    }
+
+    #[test]
+    fn get_frame_pads_short_frames_with_silence() {
+        let (status, buf) = get_frame(&[0x0102; 2], 8);
+
+        assert_eq!(status, 0);
+        assert_eq!(&buf[..8], &[0x02u8, 0x01, 0x02, 0x01, 0, 0, 0, 0]);
+        assert!(buf[8..].iter().all(|&byte| byte == GUARD));
+    }
+}
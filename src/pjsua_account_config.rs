@@ -1,12 +1,21 @@
-use std::{ffi::CString, mem::MaybeUninit};
+use std::{collections::BTreeMap, ffi::CString, mem::MaybeUninit, sync::RwLock};
 
 use crate::error::get_error_as_result;
-use crate::{ffi_assert, pjsua_call, pjsua_softphone_api};
+use crate::pjmedia::pjmedia_port_audio_sink::CustomSinkMediaPort;
+use crate::pjmedia::pjmedia_port_audio_stream::CustomStreamMediaPort;
+use crate::pjsua_call_filter::CallFilter;
+use crate::pjsua_caller_id::CallerIdConfig;
+use crate::pjsua_config::PjsuaConfigError;
+use crate::pjsua_ice::{IceConfig, TurnConfig};
+use crate::pjsua_memory_pool::PjsuaMemoryPool;
+use crate::pjsua_nat::AccountNatConfig;
+use crate::pjsua_srtp::{SrtpConfig, SrtpConfigError};
+use crate::tokio_utils::{run_on_pjsua_thread, run_on_pjsua_thread_blocking, AssertSend};
+use crate::transport::{RtpConfig, TransportId};
+use crate::{pjsua_call, pjsua_softphone_api};
 
 use pjsua::pj_str;
 
-const CSTRING_NEW_FAILED: &str = "CString::new failed!";
-
 use tokio::sync::mpsc;
 
 use super::error::PjsuaError;
@@ -15,9 +24,13 @@ pub struct AccountConfigAdded<'a> {
     account_id: pjsua::pjsua_acc_id,
     account_config: Box<pjsua::pjsua_acc_config>,
     on_incoming_call_rx: IncomingCallReceiver,
-    _cred_info: Vec<CredInfo>,
+    cred_info: Vec<CredInfo>,
     _id_owned: CString,
     _uri_owned: CString,
+    strings_owned: Vec<CString>,
+    //strings set by modify, by field. A later modify of the same field replaces them.
+    modified_strings: BTreeMap<&'static str, Vec<CString>>,
+    caller_id: CallerIdConfig,
     _pjsua_instance_started: &'a pjsua_softphone_api::PjsuaInstanceStarted,
 }
 
@@ -26,20 +39,24 @@ pub struct IncomingCallReceiver {
 }
 
 impl IncomingCallReceiver {
-    pub async fn next_call(&mut self) -> cb_user_data::OnIncomingCallSendData {
+    pub async fn next_call(&mut self) -> Result<cb_user_data::OnIncomingCallSendData, PjsuaError> {
         self.on_incoming_call_rx
             .recv()
             .await
-            .expect("channel should not be closed at that point!")
+            .ok_or(PjsuaError::ChannelClosed {
+                operation: "next_call",
+            })
     }
 }
 
 pub struct AccountConfig {
     account_config: Box<pjsua::pjsua_acc_config>,
     on_incoming_call_rx: IncomingCallReceiver,
-    _cred_info: Vec<CredInfo>,
+    cred_info: Vec<CredInfo>,
     _id_owned: CString,
     _uri_owned: CString,
+    strings_owned: Vec<CString>,
+    caller_id: CallerIdConfig,
 }
 
 struct CredInfo {
@@ -53,15 +70,21 @@ struct CredInfo {
 }
 
 impl CredInfo {
-    fn new(all_realm: &str, scheme: &str, username: &str, password: &str, domain: &str) -> Self {
+    fn try_new(
+        all_realm: &str,
+        scheme: &str,
+        username: &str,
+        password: &str,
+        domain: &str,
+    ) -> Result<Self, AccountConfigError> {
         let mut cred_info =
             unsafe { Box::new(MaybeUninit::<pjsua::pjsip_cred_info>::zeroed().assume_init()) };
 
-        let all_realm_owned = CString::new(all_realm).expect(CSTRING_NEW_FAILED);
-        let scheme_owned = CString::new(scheme).expect(CSTRING_NEW_FAILED);
-        let username_owned = CString::new(username).expect(CSTRING_NEW_FAILED);
-        let password_owned = CString::new(password).expect(CSTRING_NEW_FAILED);
-        let domain_owned = CString::new(domain).expect(CSTRING_NEW_FAILED);
+        let all_realm_owned = to_cstring("realm", all_realm)?;
+        let scheme_owned = to_cstring("scheme", scheme)?;
+        let username_owned = to_cstring("username", username)?;
+        let password_owned = to_cstring("password", password)?;
+        let domain_owned = to_cstring("domain", domain)?;
 
         unsafe {
             cred_info.realm = pj_str(all_realm_owned.as_ptr() as *mut ::std::os::raw::c_char);
@@ -71,19 +94,46 @@ impl CredInfo {
             cred_info.data_type = pjsua::pjsip_cred_data_type_PJSIP_CRED_DATA_PLAIN_PASSWD as i32;
         }
 
-        Self {
+        Ok(Self {
             cred_info,
             _all_realm_owned: all_realm_owned,
             _scheme_owned: scheme_owned,
             _username_owned: username_owned,
             _password_owned: password_owned,
             _domain_owned: domain_owned,
-        }
+        })
     }
 }
 
 impl AccountConfig {
-    pub fn new(username: &str, password: &str, domain: &str) -> Self {
+    //domain may include a port, e.g. "example.com:5060". Needs a created pjsua instance like
+    //AccountConfigBuilder::build.
+    pub fn new(username: &str, password: &str, domain: &str) -> Result<Self, AccountConfigError> {
+        if username.is_empty() {
+            return Err(AccountConfigError::EmptyField("user"));
+        }
+
+        let account_id = format!("sip:{}@{}", username, domain);
+        let registrar = format!("sip:{}", domain);
+
+        check_uri_syntax("id", &account_id)?;
+        check_uri_syntax("registrar_uri", &registrar)?;
+
+        let id = to_cstring("id", &account_id)?;
+        let uri = to_cstring("registrar_uri", &registrar)?;
+
+        parse_uris(&[("id", &id), ("registrar_uri", &uri)])?;
+
+        let cred_info0 = CredInfo::try_new("*", "digest", username, password, domain)?;
+
+        Ok(Self::from_parts(id, uri, vec![cred_info0]))
+    }
+
+    pub fn builder() -> AccountConfigBuilder {
+        AccountConfigBuilder::default()
+    }
+
+    fn from_parts(id: CString, uri: CString, cred_info: Vec<CredInfo>) -> Self {
         let (mut account_config, on_incoming_call_rx) = unsafe {
             let mut account_config =
                 Box::new(MaybeUninit::<pjsua::pjsua_acc_config>::zeroed().assume_init());
@@ -94,6 +144,7 @@ impl AccountConfig {
 
             let on_incoming_call_tx = Box::new(cb_user_data::AccountConfigUserData {
                 on_incoming_call_tx,
+                call_filter: RwLock::new(CallFilter::default()),
             });
 
             account_config.user_data =
@@ -104,9 +155,6 @@ impl AccountConfig {
             (account_config, on_incoming_call_rx)
         };
 
-        let id = CString::new(&*format!("sip:{}@{}", username, domain)).expect(CSTRING_NEW_FAILED);
-        let uri = CString::new(&*format!("sip:{}", domain)).expect(CSTRING_NEW_FAILED);
-
         let pjsua_acc_cfg = account_config.as_mut();
 
         unsafe {
@@ -114,10 +162,14 @@ impl AccountConfig {
             pjsua_acc_cfg.reg_uri = pj_str(uri.as_ptr() as *mut i8);
         }
 
-        pjsua_acc_cfg.cred_count = 1;
+        //callers pass at most one credential.
+        debug_assert!(cred_info.len() <= pjsua_acc_cfg.cred_info.len());
+
+        pjsua_acc_cfg.cred_count = cred_info.len() as u32;
 
-        let cred_info0 = CredInfo::new("*", "digest", username, password, domain);
-        pjsua_acc_cfg.cred_info[0] = *cred_info0.cred_info;
+        for (i, cred) in cred_info.iter().enumerate() {
+            pjsua_acc_cfg.cred_info[i] = *cred.cred_info;
+        }
 
         let on_incoming_call_rx = IncomingCallReceiver {
             on_incoming_call_rx,
@@ -128,76 +180,941 @@ impl AccountConfig {
             on_incoming_call_rx,
             _id_owned: id,
             _uri_owned: uri,
-            _cred_info: vec![cred_info0],
+            cred_info,
+            strings_owned: Vec::new(),
+            caller_id: CallerIdConfig::default(),
         };
 
         account_config
     }
 
-    pub(crate) fn add_to_instance_init<'a>(
-        mut self,
+    //the returned pj_str_t stays valid for as long as this AccountConfig (or the
+    //AccountConfigAdded created from it) lives, since the CString heap buffer does not move.
+    fn own_string(&mut self, string: CString) -> pjsua::pj_str_t {
+        let pj_string = unsafe { pj_str(string.as_ptr() as *mut i8) };
+        self.strings_owned.push(string);
+
+        pj_string
+    }
+
+    //the config moves to the pjsua worker and comes back with the account, if the future is
+    //dropped in between, PendingAccount deletes the account again.
+    pub(crate) async fn add_to_instance<'a>(
+        self,
         pjsua_instance_started: &'a pjsua_softphone_api::PjsuaInstanceStarted,
     ) -> Result<AccountConfigAdded<'a>, PjsuaError> {
+        let account_config = unsafe { AssertSend::new(self) };
+
+        let pending_account = run_on_pjsua_thread(move || {
+            let mut account_config = account_config.into_inner();
+            let account_id = unsafe { account_config.add_to_pjsua() }?;
+
+            let pending_account = PendingAccount {
+                account_config: Some(account_config),
+                account_id,
+            };
+
+            Ok::<_, PjsuaError>(unsafe { AssertSend::new(pending_account) })
+        })
+        .await??;
+
+        Ok(pending_account
+            .into_inner()
+            .into_added(pjsua_instance_started))
+    }
+
+    //the URIs were parsed with pjsip when the config was built.
+    unsafe fn add_to_pjsua(&mut self) -> Result<pjsua::pjsua_acc_id, PjsuaError> {
         let account_raw = self.as_mut();
 
         let mut account_id: pjsua::pjsua_acc_id = 2;
 
-        unsafe {
-            get_error_as_result(pjsua::pjsua_acc_add(
+        get_error_as_result(
+            "pjsua_acc_add",
+            pjsua::pjsua_acc_add(
                 account_raw,
                 pjsua::pj_constants__PJ_TRUE as i32,
                 &mut account_id,
-            ))?;
+            ),
+        )?;
 
-            let user_data = pjsua::pjsua_acc_get_user_data(account_id);
+        let user_data = pjsua::pjsua_acc_get_user_data(account_id);
 
-            ffi_assert!(!user_data.is_null());
+        if user_data.is_null() {
+            pjsua::pjsua_acc_del(account_id);
+
+            return Err(PjsuaError::Lifecycle {
+                operation: "pjsua_acc_get_user_data",
+                reason: "account was added without user data",
+            });
         }
 
-        eprintln!("added account. account_id: {}", account_id);
+        Ok(account_id)
+    }
+}
+
+//an account added to pjsua that is not yet owned by an AccountConfigAdded.
+struct PendingAccount {
+    account_config: Option<AccountConfig>,
+    account_id: pjsua::pjsua_acc_id,
+}
 
-        let config_added = AccountConfigAdded {
-            account_id,
-            account_config: self.account_config,
-            on_incoming_call_rx: self.on_incoming_call_rx,
-            _cred_info: self._cred_info,
-            _id_owned: self._id_owned,
-            _uri_owned: self._uri_owned,
+impl PendingAccount {
+    fn into_added(
+        mut self,
+        pjsua_instance_started: &pjsua_softphone_api::PjsuaInstanceStarted,
+    ) -> AccountConfigAdded<'_> {
+        let account_config = self
+            .account_config
+            .take()
+            .expect("account config is only taken here");
+
+        tracing::info!(acc_id = self.account_id, "account added");
+
+        AccountConfigAdded {
+            account_id: self.account_id,
+            account_config: account_config.account_config,
+            on_incoming_call_rx: account_config.on_incoming_call_rx,
+            cred_info: account_config.cred_info,
+            _id_owned: account_config._id_owned,
+            _uri_owned: account_config._uri_owned,
+            strings_owned: account_config.strings_owned,
+            modified_strings: BTreeMap::new(),
+            caller_id: account_config.caller_id,
             _pjsua_instance_started: pjsua_instance_started,
+        }
+    }
+}
+
+impl Drop for PendingAccount {
+    fn drop(&mut self) {
+        if self.account_config.is_some() {
+            tracing::debug!(
+                acc_id = self.account_id,
+                "deleting account that was never handed out"
+            );
+            delete_account(self.account_id);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SipUriScheme {
+    #[default]
+    Sip,
+    Sips,
+}
+
+impl SipUriScheme {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SipUriScheme::Sip => "sip",
+            SipUriScheme::Sips => "sips",
+        }
+    }
+}
+
+//address family used for the SIP signalling or media of an account.
+//a dual-stack account uses NoPreference: the family of the SDP answer follows the offer, and
+//for outgoing calls the family follows the resolved destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ipv6Use {
+    Disabled,
+    NoPreference,
+    PreferIpv4,
+    PreferIpv6,
+    Ipv6Only,
+}
+
+impl Ipv6Use {
+    fn as_raw(&self) -> pjsua::pjsua_ipv6_use {
+        match self {
+            Ipv6Use::Disabled => pjsua::pjsua_ipv6_use_PJSUA_IPV6_DISABLED,
+            Ipv6Use::NoPreference => pjsua::pjsua_ipv6_use_PJSUA_IPV6_ENABLED_NO_PREFERENCE,
+            Ipv6Use::PreferIpv4 => pjsua::pjsua_ipv6_use_PJSUA_IPV6_ENABLED_PREFER_IPV4,
+            Ipv6Use::PreferIpv6 => pjsua::pjsua_ipv6_use_PJSUA_IPV6_ENABLED_PREFER_IPV6,
+            Ipv6Use::Ipv6Only => pjsua::pjsua_ipv6_use_PJSUA_IPV6_ENABLED_USE_IPV6_ONLY,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UriTransportParam {
+    Udp,
+    Tcp,
+    Tls,
+}
+
+impl UriTransportParam {
+    fn as_str(&self) -> &'static str {
+        match self {
+            UriTransportParam::Udp => "udp",
+            UriTransportParam::Tcp => "tcp",
+            UriTransportParam::Tls => "tls",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountConfigError {
+    MissingField(&'static str),
+    EmptyField(&'static str),
+    InteriorNul(&'static str),
+    InvalidContactParams(&'static str, String),
+    InvalidUri(&'static str, String),
+    InvalidConfig(PjsuaConfigError),
+    InvalidSrtpConfig(SrtpConfigError),
+    TooManyEntries(&'static str, usize),
+    ModifyFailed(PjsuaError),
+    MemoryPoolCreationFailed,
+    PjsuaNotCreated,
+    Worker(PjsuaError),
+}
+
+impl std::fmt::Display for AccountConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AccountConfigError::MissingField(field) => write!(f, "missing field: {}", field),
+            AccountConfigError::EmptyField(field) => write!(f, "field is empty: {}", field),
+            AccountConfigError::InteriorNul(field) => {
+                write!(f, "field contains an interior NUL byte: {}", field)
+            }
+            AccountConfigError::InvalidContactParams(field, params) => {
+                write!(f, "{} must start with ';', got: {:?}", field, params)
+            }
+            AccountConfigError::InvalidUri(field, uri) => {
+                write!(f, "{} is not a valid SIP URI: {:?}", field, uri)
+            }
+            AccountConfigError::InvalidConfig(error) => write!(f, "{}", error),
+            AccountConfigError::InvalidSrtpConfig(error) => write!(f, "{}", error),
+            AccountConfigError::TooManyEntries(field, max) => {
+                write!(f, "too many entries in {}, at most {} allowed", field, max)
+            }
+            AccountConfigError::ModifyFailed(error) => {
+                write!(f, "pjsua_acc_modify failed: {}", error)
+            }
+            AccountConfigError::MemoryPoolCreationFailed => {
+                write!(f, "failed to create memory pool for URI validation")
+            }
+            AccountConfigError::PjsuaNotCreated => {
+                write!(f, "pjsua is not created, URIs can not be parsed")
+            }
+            AccountConfigError::Worker(error) => write!(f, "pjsua worker failed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for AccountConfigError {}
+
+fn to_cstring(field: &'static str, value: &str) -> Result<CString, AccountConfigError> {
+    CString::new(value).map_err(|_| AccountConfigError::InteriorNul(field))
+}
+
+fn checked_params(
+    field: &'static str,
+    params: Option<&str>,
+) -> Result<Option<CString>, AccountConfigError> {
+    match params {
+        None => Ok(None),
+        Some(params) if !params.starts_with(';') => Err(AccountConfigError::InvalidContactParams(
+            field,
+            params.to_string(),
+        )),
+        Some(params) => to_cstring(field, params).map(Some),
+    }
+}
+
+//syntax check that works without pjsip, so that accounts can be validated before the pjsua
+//instance is created, see AccountConfigBuilder::validate. parse_uris does the full check.
+fn check_uri_syntax(field: &'static str, uri: &str) -> Result<(), AccountConfigError> {
+    let invalid = || AccountConfigError::InvalidUri(field, uri.to_string());
+
+    //name-addr form, e.g. "Alice" <sip:alice@example.com>
+    let addr_spec = match uri.rfind('<') {
+        Some(start) => uri[start + 1..].strip_suffix('>').ok_or_else(invalid)?,
+        None => uri,
+    };
+
+    let (scheme, rest) = addr_spec.split_once(':').ok_or_else(invalid)?;
+
+    if rest.is_empty()
+        || rest
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == '<' || c == '>')
+    {
+        return Err(invalid());
+    }
+
+    match scheme.to_ascii_lowercase().as_str() {
+        "tel" => Ok(()),
+        "sip" | "sips" => {
+            let host_port = match rest.rsplit_once('@') {
+                Some(("", _)) => return Err(invalid()),
+                Some((_, host_port)) => host_port,
+                None => rest,
+            };
+
+            //URI parameters and headers follow the host.
+            let host_port = host_port.split([';', '?']).next().unwrap_or_default();
+
+            //IPv6 references are enclosed in brackets, the port follows them.
+            let (host, port) = match host_port.strip_prefix('[') {
+                Some(ipv6) => {
+                    let (host, port) = ipv6.split_once(']').ok_or_else(invalid)?;
+
+                    match port {
+                        "" => (host, None),
+                        port => (host, Some(port.strip_prefix(':').ok_or_else(invalid)?)),
+                    }
+                }
+                None => match host_port.split_once(':') {
+                    Some((host, port)) => (host, Some(port)),
+                    None => (host_port, None),
+                },
+            };
+
+            if host.is_empty() {
+                return Err(invalid());
+            }
+
+            match port.map(|port| port.parse::<u16>()) {
+                Some(Err(_)) => Err(invalid()),
+                _ => Ok(()),
+            }
+        }
+        _ => Err(invalid()),
+    }
+}
+
+//pjsip_parse_uri requires the pjsip endpoint, so this may only be called once the pjsua
+//instance has been created, on the pjsua worker.
+fn validate_sip_uri(
+    field: &'static str,
+    uri: &CString,
+    mem_pool: &PjsuaMemoryPool,
+) -> Result<(), AccountConfigError> {
+    //the parser may write into the buffer, it must not alias the CString that is passed to pjsua.
+    let mut buffer = uri.as_bytes_with_nul().to_vec();
+
+    let parsed = unsafe {
+        pjsua::pjsip_parse_uri(
+            mem_pool.raw_handle(),
+            buffer.as_mut_ptr() as *mut ::std::os::raw::c_char,
+            (buffer.len() - 1) as pjsua::pj_size_t,
+            pjsua::PJSIP_PARSE_URI_AS_NAMEADDR as u32,
+        )
+    };
+
+    match parsed.is_null() {
+        true => Err(AccountConfigError::InvalidUri(
+            field,
+            uri.to_string_lossy().into_owned(),
+        )),
+        false => Ok(()),
+    }
+}
+
+//parses the URIs with pjsip on the pjsua worker.
+fn parse_uris(uris: &[(&'static str, &CString)]) -> Result<(), AccountConfigError> {
+    if !pjsua_softphone_api::instance_created() {
+        return Err(AccountConfigError::PjsuaNotCreated);
+    }
+
+    let parsed = unsafe {
+        run_on_pjsua_thread_blocking(|| {
+            let mem_pool = PjsuaMemoryPool::new(512, 512)
+                .ok_or(AccountConfigError::MemoryPoolCreationFailed)?;
+
+            uris.iter()
+                .try_for_each(|&(field, uri)| validate_sip_uri(field, uri, &mem_pool))
+        })
+    };
+
+    parsed.map_err(AccountConfigError::Worker)?
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AccountConfigBuilder {
+    display_name: Option<String>,
+    scheme: SipUriScheme,
+    user: Option<String>,
+    password: Option<String>,
+    host: Option<String>,
+    port: Option<u16>,
+    transport: Option<UriTransportParam>,
+    registrar_uri: Option<String>,
+    contact_params: Option<String>,
+    contact_uri_params: Option<String>,
+    nat_config: AccountNatConfig,
+    ice_config: Option<IceConfig>,
+    turn_config: Option<TurnConfig>,
+    srtp_config: Option<SrtpConfig>,
+    caller_id: CallerIdConfig,
+    transport_id: Option<TransportId>,
+    ipv6_sip_use: Option<Ipv6Use>,
+    ipv6_media_use: Option<Ipv6Use>,
+    rtp_config: Option<RtpConfig>,
+}
+
+impl AccountConfigBuilder {
+    pub fn display_name(mut self, display_name: impl Into<String>) -> Self {
+        self.display_name = Some(display_name.into());
+        self
+    }
+
+    pub fn scheme(mut self, scheme: SipUriScheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn transport(mut self, transport: UriTransportParam) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    //if not set, the registrar URI is derived from scheme, host, port and transport.
+    pub fn registrar_uri(mut self, registrar_uri: impl Into<String>) -> Self {
+        self.registrar_uri = Some(registrar_uri.into());
+        self
+    }
+
+    //header parameters appended to the Contact header, e.g. ";+sip.instance=...".
+    pub fn contact_params(mut self, contact_params: impl Into<String>) -> Self {
+        self.contact_params = Some(contact_params.into());
+        self
+    }
+
+    //parameters appended to the Contact URI, e.g. ";ob".
+    pub fn contact_uri_params(mut self, contact_uri_params: impl Into<String>) -> Self {
+        self.contact_uri_params = Some(contact_uri_params.into());
+        self
+    }
+
+    pub fn nat_config(mut self, nat_config: AccountNatConfig) -> Self {
+        self.nat_config = nat_config;
+        self
+    }
+
+    //overrides the global ICE settings of MediaConfig for this account.
+    pub fn ice_config(mut self, ice_config: IceConfig) -> Self {
+        self.ice_config = Some(ice_config);
+        self
+    }
+
+    //overrides the global TURN settings of MediaConfig for this account.
+    pub fn turn_config(mut self, turn_config: TurnConfig) -> Self {
+        self.turn_config = Some(turn_config);
+        self
+    }
+
+    //overrides the global SRTP settings of PjsuaConfig for this account.
+    pub fn srtp_config(mut self, srtp_config: SrtpConfig) -> Self {
+        self.srtp_config = Some(srtp_config);
+        self
+    }
+
+    //default caller id of outgoing calls, may be overridden per call.
+    pub fn caller_id(mut self, caller_id: CallerIdConfig) -> Self {
+        self.caller_id = caller_id;
+        self
+    }
+
+    //pins the account to a transport, otherwise pjsua selects one per destination.
+    pub fn transport_id(mut self, transport_id: TransportId) -> Self {
+        self.transport_id = Some(transport_id);
+        self
+    }
+
+    pub fn ipv6_sip_use(mut self, ipv6_sip_use: Ipv6Use) -> Self {
+        self.ipv6_sip_use = Some(ipv6_sip_use);
+        self
+    }
+
+    //media of IPv6 peers requires at least NoPreference.
+    pub fn ipv6_media_use(mut self, ipv6_media_use: Ipv6Use) -> Self {
+        self.ipv6_media_use = Some(ipv6_media_use);
+        self
+    }
+
+    //RTP port range, addresses and QoS of the media of this account.
+    pub fn rtp_config(mut self, rtp_config: RtpConfig) -> Self {
+        self.rtp_config = Some(rtp_config);
+        self
+    }
+
+    fn host_port(&self) -> Result<String, AccountConfigError> {
+        let host = self
+            .host
+            .as_deref()
+            .ok_or(AccountConfigError::MissingField("host"))?;
+
+        if host.is_empty() {
+            return Err(AccountConfigError::EmptyField("host"));
+        }
+
+        //IPv6 references must be enclosed in brackets.
+        let host = match host.contains(':') && !host.starts_with('[') {
+            true => format!("[{}]", host),
+            false => host.to_string(),
         };
 
-        Ok(config_added)
+        match self.port {
+            Some(port) => Ok(format!("{}:{}", host, port)),
+            None => Ok(host),
+        }
+    }
+
+    fn uri_params(&self) -> String {
+        match self.transport {
+            Some(transport) => format!(";transport={}", transport.as_str()),
+            None => String::new(),
+        }
+    }
+
+    fn account_id(&self) -> Result<String, AccountConfigError> {
+        let uri = match self.user.as_deref() {
+            Some("") => return Err(AccountConfigError::EmptyField("user")),
+            Some(user) => format!(
+                "{}:{}@{}{}",
+                self.scheme.as_str(),
+                user,
+                self.host_port()?,
+                self.uri_params()
+            ),
+            None => format!(
+                "{}:{}{}",
+                self.scheme.as_str(),
+                self.host_port()?,
+                self.uri_params()
+            ),
+        };
+
+        match self.display_name.as_deref() {
+            Some(display_name) => {
+                let display_name = display_name.replace('\\', "\\\\").replace('"', "\\\"");
+                Ok(format!("\"{}\" <{}>", display_name, uri))
+            }
+            None => Ok(format!("<{}>", uri)),
+        }
+    }
+
+    fn registrar(&self) -> Result<String, AccountConfigError> {
+        match self.registrar_uri.as_deref() {
+            Some("") => Err(AccountConfigError::EmptyField("registrar_uri")),
+            Some(registrar_uri) => Ok(registrar_uri.to_string()),
+            None => Ok(format!(
+                "{}:{}{}",
+                self.scheme.as_str(),
+                self.host_port()?,
+                self.uri_params()
+            )),
+        }
+    }
+
+    fn credentials(&self) -> Result<Vec<CredInfo>, AccountConfigError> {
+        match (self.user.as_deref(), self.password.as_deref()) {
+            (Some(user), Some(password)) => Ok(vec![CredInfo::try_new(
+                "*",
+                "digest",
+                user,
+                password,
+                &self.host_port()?,
+            )?]),
+            (None, Some(_)) => Err(AccountConfigError::MissingField("user")),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    //checks the URIs, credentials and contact parameters without touching pjsua, so it can run
+    //before the pjsua instance is created. build parses the URIs with pjsip in addition.
+    pub fn validate(&self) -> Result<(), AccountConfigError> {
+        let account_id = self.account_id()?;
+        let registrar = self.registrar()?;
+
+        check_uri_syntax("id", &account_id)?;
+        check_uri_syntax("registrar_uri", &registrar)?;
+
+        to_cstring("id", &account_id)?;
+        to_cstring("registrar_uri", &registrar)?;
+
+        checked_params("contact_params", self.contact_params.as_deref())?;
+        checked_params("contact_uri_params", self.contact_uri_params.as_deref())?;
+
+        self.credentials()?;
+
+        Ok(())
+    }
+
+    //needs a created pjsua instance, the URIs are parsed with pjsip_parse_uri.
+    pub fn build(self) -> Result<AccountConfig, AccountConfigError> {
+        self.validate()?;
+
+        let id = to_cstring("id", &self.account_id()?)?;
+        let uri = to_cstring("registrar_uri", &self.registrar()?)?;
+
+        parse_uris(&[("id", &id), ("registrar_uri", &uri)])?;
+
+        let contact_params = checked_params("contact_params", self.contact_params.as_deref())?;
+        let contact_uri_params =
+            checked_params("contact_uri_params", self.contact_uri_params.as_deref())?;
+
+        let cred_info = self.credentials()?;
+
+        let mut account_config = AccountConfig::from_parts(id, uri, cred_info);
+
+        self.nat_config.apply(account_config.as_mut());
+
+        account_config.caller_id = self.caller_id.clone();
+
+        if let Some(transport_id) = self.transport_id {
+            account_config.as_mut().transport_id = transport_id.as_raw();
+        }
+
+        if let Some(ipv6_sip_use) = self.ipv6_sip_use {
+            account_config.as_mut().ipv6_sip_use = ipv6_sip_use.as_raw();
+        }
+
+        if let Some(ipv6_media_use) = self.ipv6_media_use {
+            account_config.as_mut().ipv6_media_use = ipv6_media_use.as_raw();
+        }
+
+        if let Some(rtp_config) = &self.rtp_config {
+            let (rtp_config, strings_owned) = rtp_config
+                .to_raw()
+                .map_err(AccountConfigError::InvalidConfig)?;
+
+            account_config.strings_owned.extend(strings_owned);
+            account_config.as_mut().rtp_cfg = rtp_config;
+        }
+
+        if let Some(ice_config) = &self.ice_config {
+            let pjsua_acc_cfg = account_config.as_mut();
+
+            pjsua_acc_cfg.ice_cfg_use = pjsua::pjsua_ice_config_use_PJSUA_ICE_CONFIG_USE_CUSTOM;
+            pjsua_acc_cfg.ice_cfg = ice_config.to_raw();
+        }
+
+        if let Some(turn_config) = &self.turn_config {
+            let (turn_config, strings_owned) = turn_config
+                .to_raw()
+                .map_err(AccountConfigError::InvalidConfig)?;
+
+            account_config.strings_owned.extend(strings_owned);
+
+            let pjsua_acc_cfg = account_config.as_mut();
+
+            pjsua_acc_cfg.turn_cfg_use = pjsua::pjsua_turn_config_use_PJSUA_TURN_CONFIG_USE_CUSTOM;
+            pjsua_acc_cfg.turn_cfg = turn_config;
+        }
+
+        if let Some(contact_params) = contact_params {
+            let contact_params = account_config.own_string(contact_params);
+            account_config.as_mut().contact_params = contact_params;
+        }
+
+        if let Some(contact_uri_params) = contact_uri_params {
+            let contact_uri_params = account_config.own_string(contact_uri_params);
+            account_config.as_mut().contact_uri_params = contact_uri_params;
+        }
+
+        if let Some(srtp_config) = &self.srtp_config {
+            let srtp_config = srtp_config
+                .to_raw()
+                .map_err(AccountConfigError::InvalidSrtpConfig)?;
+
+            let pjsua_acc_cfg = account_config.as_mut();
+
+            pjsua_acc_cfg.use_srtp = srtp_config.use_srtp;
+            pjsua_acc_cfg.srtp_opt = srtp_config.srtp_opt;
+
+            if let Some(secure_signaling) = srtp_config.secure_signaling {
+                pjsua_acc_cfg.srtp_secure_signaling = secure_signaling;
+            }
+        }
+
+        Ok(account_config)
+    }
+}
+
+pub struct AccountConfigModifier {
+    account_config: pjsua::pjsua_acc_config,
+    cred_info: Option<Vec<CredInfo>>,
+    modified_strings: BTreeMap<&'static str, Vec<CString>>,
+}
+
+impl AccountConfigModifier {
+    //the strings replace the ones the field owned before, so setting a field repeatedly
+    //doesn't accumulate them.
+    fn own_strings(&mut self, field: &'static str, strings: Vec<CString>) -> Vec<pjsua::pj_str_t> {
+        let pj_strings = strings
+            .iter()
+            .map(|string| unsafe { pj_str(string.as_ptr() as *mut i8) })
+            .collect();
+        self.modified_strings.insert(field, strings);
+
+        pj_strings
+    }
+
+    //replaces all credentials of the account.
+    pub fn set_credentials(
+        &mut self,
+        realm: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<(), AccountConfigError> {
+        let cred_info = CredInfo::try_new(realm, "digest", username, password, "")?;
+
+        self.account_config.cred_count = 1;
+        self.account_config.cred_info[0] = *cred_info.cred_info;
+        self.cred_info = Some(vec![cred_info]);
+
+        Ok(())
+    }
+
+    pub fn set_registrar_uri(&mut self, registrar_uri: &str) -> Result<(), AccountConfigError> {
+        let registrar_uri = to_cstring("registrar_uri", registrar_uri)?;
+        parse_uris(&[("registrar_uri", &registrar_uri)])?;
+
+        self.account_config.reg_uri = self.own_strings("registrar_uri", vec![registrar_uri])[0];
+
+        Ok(())
+    }
+
+    //proxies are given as URIs, e.g. "sip:proxy.example.com;lr".
+    pub fn set_proxies(&mut self, proxies: &[&str]) -> Result<(), AccountConfigError> {
+        let max = self.account_config.proxy.len();
+
+        if proxies.len() > max {
+            return Err(AccountConfigError::TooManyEntries("proxy", max));
+        }
+
+        let proxies = proxies
+            .iter()
+            .map(|proxy| to_cstring("proxy", proxy))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        parse_uris(
+            &proxies
+                .iter()
+                .map(|proxy| ("proxy", proxy))
+                .collect::<Vec<_>>(),
+        )?;
+
+        let proxies = self.own_strings("proxy", proxies);
+
+        self.account_config.proxy_cnt = proxies.len() as u32;
+
+        for (i, proxy) in proxies.into_iter().enumerate() {
+            self.account_config.proxy[i] = proxy;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_contact_params(&mut self, contact_params: &str) -> Result<(), AccountConfigError> {
+        let contact_params = checked_params("contact_params", Some(contact_params))?;
+
+        if let Some(contact_params) = contact_params {
+            self.account_config.contact_params =
+                self.own_strings("contact_params", vec![contact_params])[0];
+        }
+
+        Ok(())
+    }
+
+    pub fn set_contact_uri_params(
+        &mut self,
+        contact_uri_params: &str,
+    ) -> Result<(), AccountConfigError> {
+        let contact_uri_params = checked_params("contact_uri_params", Some(contact_uri_params))?;
+
+        if let Some(contact_uri_params) = contact_uri_params {
+            self.account_config.contact_uri_params =
+                self.own_strings("contact_uri_params", vec![contact_uri_params])[0];
+        }
+
+        Ok(())
+    }
+
+    pub fn set_reg_timeout(&mut self, reg_timeout: std::time::Duration) {
+        self.account_config.reg_timeout = reg_timeout.as_secs() as u32;
+    }
+
+    pub fn set_reg_retry_interval(&mut self, reg_retry_interval: std::time::Duration) {
+        self.account_config.reg_retry_interval = reg_retry_interval.as_secs() as u32;
+    }
+
+    pub fn set_nat_config(&mut self, nat_config: &AccountNatConfig) {
+        nat_config.apply(&mut self.account_config);
     }
 }
 
 impl<'a> AccountConfigAdded<'a> {
     pub async fn next_call(&mut self) -> Result<pjsua_call::PjsuaIncomingCall<'a>, PjsuaError> {
-        let (account_id, call_id) = self.on_incoming_call_rx.next_call().await;
-        pjsua_call::PjsuaIncomingCall::new(account_id, call_id, self._pjsua_instance_started)
+        let (account_id, call_id) = self.on_incoming_call_rx.next_call().await?;
+        pjsua_call::PjsuaIncomingCall::new(account_id, call_id, self._pjsua_instance_started).await
+    }
+
+    //caller_id overrides the caller id the account was configured with.
+    pub async fn make_call(
+        &self,
+        dst_uri: &str,
+        caller_id: Option<&CallerIdConfig>,
+        sink: CustomSinkMediaPort,
+        stream: CustomStreamMediaPort,
+    ) -> Result<pjsua_call::PjsuaCall<'a>, PjsuaError> {
+        let caller_id = caller_id.unwrap_or(&self.caller_id);
+
+        //the headers are created on the pjsua worker, together with the call.
+        let caller_id = match caller_id.is_empty() {
+            true => None,
+            false => Some((
+                caller_id.clone(),
+                self._id_owned.to_string_lossy().into_owned(),
+            )),
+        };
+
+        pjsua_call::PjsuaCall::make_call(
+            self.account_id,
+            dst_uri,
+            caller_id,
+            sink,
+            stream,
+            self._pjsua_instance_started,
+        )
+        .await
+    }
+
+    //replaces the rules that on_incoming_call checks before a call reaches next_call.
+    pub fn set_call_filter(&self, call_filter: CallFilter) -> Result<(), PjsuaError> {
+        let account_id = self.account_id;
+
+        let user_data = unsafe {
+            run_on_pjsua_thread_blocking(|| {
+                pjsua::pjsua_acc_get_user_data(account_id)
+                    as *const cb_user_data::AccountConfigUserData
+            })?
+            .as_ref()
+        };
+
+        let user_data = user_data.ok_or(PjsuaError::Lifecycle {
+            operation: "pjsua_acc_get_user_data",
+            reason: "account has no user data",
+        })?;
+
+        match user_data.call_filter.write() {
+            Ok(mut current) => *current = call_filter,
+            Err(poisoned) => *poisoned.into_inner() = call_filter,
+        }
+
+        Ok(())
+    }
+
+    //applies the changes with pjsua_acc_modify, keeping calls and the incoming call receiver.
+    //if either f or pjsua_acc_modify fails, the account is left unchanged.
+    pub fn modify<F>(&mut self, f: F) -> Result<(), AccountConfigError>
+    where
+        F: FnOnce(&mut AccountConfigModifier) -> Result<(), AccountConfigError>,
+    {
+        let mut modifier = AccountConfigModifier {
+            account_config: *self.account_config,
+            cred_info: None,
+            modified_strings: BTreeMap::new(),
+        };
+
+        f(&mut modifier)?;
+
+        let account_id = self.account_id;
+
+        unsafe {
+            run_on_pjsua_thread_blocking(|| {
+                get_error_as_result(
+                    "pjsua_acc_modify",
+                    pjsua::pjsua_acc_modify(account_id, &modifier.account_config),
+                )
+            })
+            .and_then(|modified| modified)
+            .map_err(AccountConfigError::ModifyFailed)?;
+        }
+
+        *self.account_config = modifier.account_config;
+        //pjsua_acc_modify copied the config, the replaced strings are no longer referenced.
+        self.modified_strings.extend(modifier.modified_strings);
+
+        if let Some(cred_info) = modifier.cred_info {
+            self.cred_info = cred_info;
+        }
+
+        Ok(())
     }
 }
 
 impl<'a> Drop for AccountConfigAdded<'a> {
     fn drop(&mut self) {
-        unsafe {
-            let on_incoming_call_tx = pjsua::pjsua_acc_get_user_data(self.account_id)
+        delete_account(self.account_id);
+    }
+}
+
+//deletes the account and frees its user data once pjsua no longer refers to it.
+fn delete_account(account_id: pjsua::pjsua_acc_id) {
+    let deleted = unsafe {
+        run_on_pjsua_thread_blocking(|| {
+            let on_incoming_call_tx = pjsua::pjsua_acc_get_user_data(account_id)
                 as *mut cb_user_data::AccountConfigUserData;
+            let status = get_error_as_result("pjsua_acc_del", pjsua::pjsua_acc_del(account_id));
 
-            assert!(!on_incoming_call_tx.is_null());
-            let status = get_error_as_result(pjsua::pjsua_acc_del(self.account_id));
+            (on_incoming_call_tx, status)
+        })
+    };
+
+    let on_incoming_call_tx = match deleted {
+        Ok((on_incoming_call_tx, status)) => {
             if let Err(e) = status {
-                eprintln!("error while dropping account: {}", e);
+                tracing::warn!(acc_id = account_id, error = %e, "failed to delete account");
             }
 
-            //assuming that on_incoming_call cb is neigther in progress nor to be called again
-            //this assumption is made on the premises of:
-            //https://docs.pjsip.org/en/latest/_static/PJSIP-Dev-Guide.pdf#page=13 [[Thread Safety]]
-
-            eprintln!("dropping on_incoming_call_tx...");
-            let on_incoming_call_tx = Box::from_raw(on_incoming_call_tx);
-            drop(on_incoming_call_tx);
+            on_incoming_call_tx
+        }
+        Err(e) => {
+            tracing::warn!(acc_id = account_id, error = %e, "failed to delete account");
+            return;
         }
+    };
+
+    //add_to_pjsua doesn't hand out accounts without user data, pjsua lost it.
+    if on_incoming_call_tx.is_null() {
+        tracing::error!(acc_id = account_id, "account has no user data");
+        return;
     }
+
+    //assuming that on_incoming_call cb is neigther in progress nor to be called again
+    //this assumption is made on the premises of:
+    //https://docs.pjsip.org/en/latest/_static/PJSIP-Dev-Guide.pdf#page=13 [[Thread Safety]]
+
+    let on_incoming_call_tx = unsafe { Box::from_raw(on_incoming_call_tx) };
+    drop(on_incoming_call_tx);
 }
 
 impl<'a> AsMut<pjsua::pjsua_acc_config> for AccountConfigAdded<'a> {
@@ -213,6 +1130,8 @@ impl AsMut<pjsua::pjsua_acc_config> for AccountConfig {
 }
 
 pub(crate) mod cb_user_data {
+    use crate::pjsua_call_filter::CallFilter;
+    use std::sync::RwLock;
     use tokio::sync::mpsc::Sender;
 
     #[allow(unused_parens)]
@@ -220,5 +1139,107 @@ pub(crate) mod cb_user_data {
 
     pub struct AccountConfigUserData {
         pub(crate) on_incoming_call_tx: Sender<OnIncomingCallSendData>,
+        pub(crate) call_filter: RwLock<CallFilter>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_valid(uri: &str) -> bool {
+        check_uri_syntax("id", uri).is_ok()
+    }
+
+    #[test]
+    fn check_uri_syntax_user_at_host() {
+        assert!(is_valid("sip:alice@example.com"));
+        assert!(is_valid("sips:alice@example.com"));
+        assert!(is_valid("\"Alice\" <sip:alice@example.com>"));
+        assert!(is_valid("sip:alice:secret@example.com"));
+        assert!(is_valid("tel:+15551234"));
+
+        assert!(!is_valid("sip:alice@"));
+        assert!(!is_valid("sip:alice @example.com"));
+        assert!(!is_valid("<sip:alice@example.com"));
+        assert!(!is_valid("http://example.com"));
+        assert!(!is_valid("alice@example.com"));
+    }
+
+    #[test]
+    fn check_uri_syntax_host_port() {
+        assert!(is_valid("sip:example.com:5060"));
+        assert!(is_valid("sip:alice@192.0.2.1:5060"));
+
+        assert!(!is_valid("sip:example.com:"));
+        assert!(!is_valid("sip:example.com:sip"));
+        assert!(!is_valid("sip:example.com:65536"));
+        assert!(!is_valid("sip::5060"));
+        assert!(!is_valid("sip:alice@:5060"));
+    }
+
+    #[test]
+    fn check_uri_syntax_ipv6_brackets() {
+        assert!(is_valid("sip:[::1]"));
+        assert!(is_valid("sip:alice@[2001:db8::1]:5060"));
+
+        assert!(!is_valid("sip:alice@::1"));
+        assert!(!is_valid("sip:alice@2001:db8::1"));
+        assert!(!is_valid("sip:alice@[::1"));
+        assert!(!is_valid("sip:alice@[::1]5060"));
+        assert!(!is_valid("sip:alice@[]:5060"));
+    }
+
+    #[test]
+    fn check_uri_syntax_transport_param() {
+        assert!(is_valid("sip:example.com;transport=tcp"));
+        assert!(is_valid("<sip:alice@[::1]:5061;transport=tls>"));
+        assert!(is_valid("sip:alice@example.com:5060;transport=udp;lr"));
+
+        assert!(!is_valid("sip:;transport=tcp"));
+        assert!(!is_valid("sip:alice@;transport=tcp"));
+        assert!(!is_valid("sip:alice@example.com:;transport=tcp"));
+    }
+
+    #[test]
+    fn check_uri_syntax_empty_user() {
+        assert!(!is_valid("sip:@example.com"));
+        assert!(!is_valid("<sip:@[::1]:5060>"));
+    }
+
+    #[test]
+    fn validate_without_pjsua() {
+        let builder = AccountConfig::builder()
+            .user("alice")
+            .host("::1")
+            .port(5060);
+        assert_eq!(builder.validate(), Ok(()));
+
+        let builder = AccountConfig::builder().user("").host("example.com");
+        assert_eq!(
+            builder.validate(),
+            Err(AccountConfigError::EmptyField("user"))
+        );
+
+        let builder = AccountConfig::builder()
+            .user("alice")
+            .host("example.com")
+            .registrar_uri("sip:@example.com");
+        assert_eq!(
+            builder.validate(),
+            Err(AccountConfigError::InvalidUri(
+                "registrar_uri",
+                "sip:@example.com".to_string()
+            ))
+        );
+
+        let builder = AccountConfig::builder()
+            .user("alice")
+            .password("se\0cret")
+            .host("example.com");
+        assert_eq!(
+            builder.validate(),
+            Err(AccountConfigError::InteriorNul("password"))
+        );
     }
 }
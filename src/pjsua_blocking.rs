@@ -0,0 +1,69 @@
+//blocking facade for users without an async runtime, e.g. CLI tools and test harnesses.
+//Every call parks the calling thread until the underlying future completes, timers fall back
+//to tokio_utils::ThreadRuntime outside of a tokio runtime.
+//Don't call these from within an async task, they would block the executor thread.
+
+use crate::error::PjsuaError;
+use crate::pjsua_account_config::{AccountConfig, AccountConfigAdded};
+use crate::pjsua_call::{CallEvent, PjsuaCall, PjsuaIncomingCall};
+use crate::pjsua_nat::NatDetectResult;
+use crate::pjsua_softphone_api::{
+    PjsuaInstanceStarted, ShutdownIncomplete, Softphone, SoftphoneError,
+};
+use crate::transport::{TransportEvent, TransportEventReceiver};
+
+use std::future::Future;
+use std::time::Duration;
+
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    futures::executor::block_on(future)
+}
+
+impl PjsuaInstanceStarted {
+    pub fn add_account_blocking(
+        &self,
+        account: AccountConfig,
+    ) -> Result<AccountConfigAdded, PjsuaError> {
+        block_on(self.add_account(account))
+    }
+
+    pub fn detect_nat_type_blocking(&self) -> Result<NatDetectResult, PjsuaError> {
+        block_on(self.detect_nat_type())
+    }
+
+    pub fn shutdown_blocking(&self, timeout: Duration) -> Result<(), ShutdownIncomplete> {
+        block_on(self.shutdown(timeout))
+    }
+}
+
+impl Softphone {
+    pub fn add_accounts_blocking(&self) -> Result<Vec<AccountConfigAdded>, SoftphoneError> {
+        block_on(self.add_accounts())
+    }
+
+    pub fn shutdown_blocking(&self, timeout: Duration) -> Result<(), ShutdownIncomplete> {
+        block_on(self.shutdown(timeout))
+    }
+}
+
+impl<'a> AccountConfigAdded<'a> {
+    pub fn next_call_blocking(&mut self) -> Result<PjsuaIncomingCall<'a>, PjsuaError> {
+        block_on(self.next_call())
+    }
+}
+
+impl<'a> PjsuaCall<'a> {
+    pub fn await_hangup_blocking(self) -> Result<(), PjsuaError> {
+        block_on(self.await_hangup())
+    }
+
+    pub fn next_event_blocking(&mut self) -> Option<CallEvent> {
+        block_on(self.next_event())
+    }
+}
+
+impl TransportEventReceiver {
+    pub fn next_event_blocking(&mut self) -> Option<TransportEvent> {
+        block_on(self.next_event())
+    }
+}
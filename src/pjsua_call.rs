@@ -3,11 +3,19 @@ use crate::pjsua_softphone_api;
 use super::error::{get_error_as_result, PjsuaError};
 use std::ptr;
 
-use super::tokio_utils::spawn_blocking_pjsua;
+use super::tokio_utils::{run_on_pjsua_thread, run_on_pjsua_thread_blocking, AssertSend};
 
+use std::ffi::CString;
 use std::mem::MaybeUninit;
+use std::task::Poll;
 
-use super::pjsua_memory_pool::PjsuaMemoryPool;
+use super::pjmedia::pjmedia_api::ConfBridgePort;
+use super::pjsua_caller_id::CallerIdConfig;
+use super::pjsua_ice::IceState;
+use super::pjsua_memory_pool::{
+    MemoryPoolStats, PjsuaMemoryPool, DEFAULT_INCREMENT_SIZE, DEFAULT_INIT_SIZE,
+};
+use super::pjsua_srtp::{self, MediaEncryption};
 
 pub(crate) mod answer_code {
     pub trait AnswerCode: Send + 'static {
@@ -37,7 +45,7 @@ fn accept_incoming(
         let status =
             pjsua::pjsua_call_answer(call_id, answer_state.as_u32(), ptr::null(), ptr::null());
 
-        get_error_as_result(status)?;
+        get_error_as_result("pjsua_call_answer", status)?;
     }
 
     Ok(())
@@ -46,7 +54,7 @@ fn accept_incoming(
 fn reject_incoming(call_id: pjsua::pjsua_call_id) -> Result<(), PjsuaError> {
     unsafe {
         let status = pjsua::pjsua_call_hangup(call_id, 486, ptr::null(), ptr::null());
-        get_error_as_result(status)?;
+        get_error_as_result("pjsua_call_hangup", status)?;
     }
 
     Ok(())
@@ -58,7 +66,7 @@ fn hangup_call(call_id: pjsua::pjsua_call_id) -> Result<(), PjsuaError> {
     }
     unsafe {
         let status = pjsua::pjsua_call_hangup(call_id, 200, ptr::null(), ptr::null());
-        get_error_as_result(status)?;
+        get_error_as_result("pjsua_call_hangup", status)?;
     }
 
     Ok(())
@@ -71,7 +79,7 @@ pub(crate) fn get_call_info(
     let call_info = unsafe {
         let mut call_info = MaybeUninit::<pjsua::pjsua_call_info>::zeroed().assume_init();
         let status = pjsua::pjsua_call_get_info(call_id, &mut call_info);
-        get_error_as_result(status)?;
+        get_error_as_result("pjsua_call_get_info", status)?;
 
         call_info
     };
@@ -79,6 +87,29 @@ pub(crate) fn get_call_info(
     Ok(call_info)
 }
 
+//calls f with the transport specific info of the given type, e.g. pjmedia_ice_transport_info
+//for PJMEDIA_TRANSPORT_TYPE_ICE. T must match transport_type.
+pub(crate) unsafe fn with_transport_specific_info<T, R>(
+    call_id: pjsua::pjsua_call_id,
+    media_index: u32,
+    transport_type: pjsua::pjmedia_transport_type,
+    f: impl FnOnce(&T) -> R,
+) -> Option<R> {
+    let mut transport_info = MaybeUninit::<pjsua::pjmedia_transport_info>::zeroed().assume_init();
+
+    let status =
+        pjsua::pjsua_call_get_med_transport_info(call_id, media_index, &mut transport_info);
+
+    get_error_as_result("pjsua_call_get_med_transport_info", status).ok()?;
+
+    transport_info
+        .spc_info
+        .iter()
+        .take(transport_info.specific_info_cnt as usize)
+        .find(|spc_info| spc_info.type_ == transport_type)
+        .map(|spc_info| f(&*(spc_info.buffer.as_ptr() as *const T)))
+}
+
 fn is_call_active(call_id: pjsua::pjsua_call_id) -> bool {
     let active = unsafe { pjsua::pjsua_call_is_active(call_id) };
 
@@ -89,9 +120,10 @@ pub(crate) fn get_call_conf_port(call_id: pjsua::pjsua_call_id) -> Result<i32, P
     let conf_slot = unsafe { pjsua::pjsua_call_get_conf_port(call_id) };
 
     match conf_slot {
-        pjsua::pjsua_invalid_id_const__PJSUA_INVALID_ID => Err(PjsuaError {
-            code: -1,
-            message: "Invalid conf slot".to_string(),
+        pjsua::pjsua_invalid_id_const__PJSUA_INVALID_ID => Err(PjsuaError::InvalidState {
+            operation: "pjsua_call_get_conf_port",
+            expected: "call with active media".to_string(),
+            actual: "no conference slot".to_string(),
         }),
         _ => Ok(conf_slot),
     }
@@ -104,70 +136,220 @@ pub struct PjsuaCallHandle<'a> {
     _user_data: Box<cb_user_data::StateChangedUserData>,
     _pjsua_instance_started: &'a pjsua_softphone_api::PjsuaInstanceStarted,
     state_changed_rx: CallStateReceiver,
+    media_event_rx: MediaEventReceiver,
     call_media_data_tx: Option<tokio_oneshot::Sender<CallMediaData>>,
 }
 
 impl<'a> PjsuaCallHandle<'a> {
-    pub fn new(
-        call_id: pjsua::pjsua_call_id,
-        pjsua_instance_started: &'a pjsua_softphone_api::PjsuaInstanceStarted,
-    ) -> Result<Self, PjsuaError> {
-        let (state_changed_tx, state_changed_rx) = tokio::sync::mpsc::channel(3);
+    fn user_data() -> (
+        Box<cb_user_data::StateChangedUserData>,
+        CallStateReceiver,
+        MediaEventReceiver,
+        tokio_oneshot::Sender<CallMediaData>,
+    ) {
+        //unbounded, so that pjsip callbacks never have to drop or block on a slow consumer.
+        let (state_changed_tx, state_changed_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (media_event_tx, media_event_rx) = tokio::sync::mpsc::channel(MEDIA_EVENT_CAPACITY);
         let (call_media_data_tx, call_media_data_rx) = tokio_oneshot::channel();
 
-        let mut user_data = Box::new(cb_user_data::StateChangedUserData {
+        let user_data = Box::new(cb_user_data::StateChangedUserData {
             on_state_changed_tx: state_changed_tx,
+            on_media_event_tx: media_event_tx,
             call_media_data_rx: Some(call_media_data_rx),
         });
 
-        let raw_user_data = user_data.as_mut() as *mut cb_user_data::StateChangedUserData;
+        (
+            user_data,
+            state_changed_rx,
+            media_event_rx,
+            call_media_data_tx,
+        )
+    }
+
+    pub async fn new(
+        call_id: pjsua::pjsua_call_id,
+        pjsua_instance_started: &'a pjsua_softphone_api::PjsuaInstanceStarted,
+    ) -> Result<Self, PjsuaError> {
+        let (user_data, state_changed_rx, media_event_rx, call_media_data_tx) = Self::user_data();
 
-        unsafe {
-            eprintln!("Setting user data for call {:?}...", call_id);
-            let status =
-                pjsua::pjsua_call_set_user_data(call_id, raw_user_data as *mut std::ffi::c_void);
+        let pending_call = run_on_pjsua_thread(move || {
+            let mut user_data = user_data;
+            let raw_user_data = user_data.as_mut() as *mut cb_user_data::StateChangedUserData;
 
-            get_error_as_result(status)?;
-        }
+            unsafe {
+                let status = pjsua::pjsua_call_set_user_data(
+                    call_id,
+                    raw_user_data as *mut std::ffi::c_void,
+                );
+
+                get_error_as_result("pjsua_call_set_user_data", status)?;
+            }
+
+            Ok::<_, PjsuaError>(PendingCall {
+                call_id,
+                user_data: Some(user_data),
+            })
+        })
+        .await??;
+
+        let (call_id, user_data) = pending_call.into_parts();
 
         Ok(Self {
             call_id,
             _user_data: user_data,
             state_changed_rx,
+            media_event_rx,
             call_media_data_tx: Some(call_media_data_tx),
             _pjsua_instance_started: pjsua_instance_started,
         })
     }
 
+    //call_media_data is handed over before the INVITE is sent, so that on_call_media_state
+    //always finds it, no matter how fast the remote answers. The caller id headers are created
+    //with the call, on the pjsua worker.
+    async fn make_call(
+        account_id: pjsua::pjsua_acc_id,
+        dst_uri: CString,
+        caller_id: Option<(CallerIdConfig, String)>,
+        call_media_data: CallMediaData,
+        pjsua_instance_started: &'a pjsua_softphone_api::PjsuaInstanceStarted,
+    ) -> Result<Self, PjsuaError> {
+        let (user_data, state_changed_rx, media_event_rx, call_media_data_tx) = Self::user_data();
+
+        call_media_data_tx
+            .send(call_media_data)
+            .expect("receiver is owned by user_data");
+
+        let pending_call = run_on_pjsua_thread(move || {
+            let msg_data = caller_id
+                .map(|(caller_id, account_uri)| caller_id.to_msg_data(&account_uri))
+                .transpose()?;
+
+            let msg_data = match &msg_data {
+                Some(msg_data) => msg_data.as_ref() as *const pjsua::pjsua_msg_data,
+                None => ptr::null(),
+            };
+
+            let mut user_data = user_data;
+            let raw_user_data = user_data.as_mut() as *mut cb_user_data::StateChangedUserData;
+
+            let mut call_id: pjsua::pjsua_call_id = pjsua::pjsua_invalid_id_const__PJSUA_INVALID_ID;
+
+            unsafe {
+                let dst_uri = pjsua::pj_str(dst_uri.as_ptr() as *mut i8);
+
+                get_error_as_result(
+                    "pjsua_call_make_call",
+                    pjsua::pjsua_call_make_call(
+                        account_id,
+                        &dst_uri,
+                        ptr::null(),
+                        raw_user_data as *mut std::ffi::c_void,
+                        msg_data,
+                        &mut call_id,
+                    ),
+                )?;
+            }
+
+            Ok::<_, PjsuaError>(PendingCall {
+                call_id,
+                user_data: Some(user_data),
+            })
+        })
+        .await??;
+
+        let (call_id, user_data) = pending_call.into_parts();
+
+        tracing::info!(acc_id = account_id, call_id, "outgoing call created");
+
+        Ok(Self {
+            call_id,
+            _user_data: user_data,
+            state_changed_rx,
+            media_event_rx,
+            call_media_data_tx: None,
+            _pjsua_instance_started: pjsua_instance_started,
+        })
+    }
+
     pub(crate) fn get_conf_port_slot(&self) -> Result<pjsua::pjsua_conf_port_id, PjsuaError> {
         get_call_conf_port(self.call_id)
     }
 
     async fn answer(&self, answer_code: impl answer_code::AnswerCode) -> Result<(), PjsuaError> {
         let call_id = self.call_id;
-        spawn_blocking_pjsua(move || {
+        run_on_pjsua_thread(move || {
             accept_incoming(call_id, answer_code)?;
 
             Ok::<(), PjsuaError>(())
         })
-        .await
-        .unwrap()?;
+        .await??;
 
         Ok(())
     }
 
+    //state changes take precedence over informational events.
+    async fn next_event(&mut self) -> Option<CallEvent> {
+        std::future::poll_fn(|cx| {
+            let state_changed_closed = match self.state_changed_rx.poll_recv(cx) {
+                Poll::Ready(Some((_, call_event))) => return Poll::Ready(Some(call_event)),
+                Poll::Ready(None) => true,
+                Poll::Pending => false,
+            };
+
+            match self.media_event_rx.poll_recv(cx) {
+                Poll::Ready(Some((_, call_event))) => Poll::Ready(Some(call_event)),
+                Poll::Ready(None) if state_changed_closed => Poll::Ready(None),
+                _ => Poll::Pending,
+            }
+        })
+        .await
+    }
+
     fn hangup(self) {}
 }
 
 impl<'a> Drop for PjsuaCallHandle<'a> {
     fn drop(&mut self) {
-        eprintln!("Dropping PjsuaCallHandle");
         //note: this will hangup the call if it's still active AND prevent any futher usafe of
         //on_state_changed. Then it follows that user_data will no longer be used.
+        hangup_on_drop(self.call_id);
+    }
+}
+
+fn hangup_on_drop(call_id: pjsua::pjsua_call_id) {
+    let hangup = unsafe { run_on_pjsua_thread_blocking(|| hangup_call(call_id)) };
+
+    if let Err(e) = hangup.and_then(|hangup| hangup) {
+        tracing::warn!(call_id, error = %e, "failed to hang up call on drop");
+    }
+}
+
+//a call pjsua refers the user data of, on its way back from the pjsua worker. If the future
+//waiting for it is dropped, the call is hung up before the user data is freed.
+struct PendingCall {
+    call_id: pjsua::pjsua_call_id,
+    user_data: Option<Box<cb_user_data::StateChangedUserData>>,
+}
 
-        hangup_call(self.call_id).expect("Failed to reject incoming call");
+impl PendingCall {
+    fn into_parts(
+        mut self,
+    ) -> (
+        pjsua::pjsua_call_id,
+        Box<cb_user_data::StateChangedUserData>,
+    ) {
+        let user_data = self.user_data.take().expect("user data is only taken here");
 
-        eprintln!("Dropped PjsuaCallHandle");
+        (self.call_id, user_data)
+    }
+}
+
+impl Drop for PendingCall {
+    fn drop(&mut self) {
+        if self.user_data.is_some() {
+            hangup_on_drop(self.call_id);
+        }
     }
 }
 
@@ -178,12 +360,12 @@ pub struct PjsuaIncomingCall<'a> {
 }
 
 impl<'a> PjsuaIncomingCall<'a> {
-    pub(crate) fn new(
+    pub(crate) async fn new(
         account_id: pjsua::pjsua_acc_id,
         call_id: pjsua::pjsua_call_id,
         pjsua_instance_started: &'a pjsua_softphone_api::PjsuaInstanceStarted,
     ) -> Result<Self, PjsuaError> {
-        let call_handle = PjsuaCallHandle::new(call_id, pjsua_instance_started)?;
+        let call_handle = PjsuaCallHandle::new(call_id, pjsua_instance_started).await?;
 
         Ok(Self {
             call_handle: Some(call_handle),
@@ -199,42 +381,58 @@ impl<'a> PjsuaIncomingCall<'a> {
     pub async fn reject(mut self) -> Result<(), PjsuaError> {
         let call_id = self.call_handle.take().unwrap().call_id;
 
-        spawn_blocking_pjsua(move || {
+        run_on_pjsua_thread(move || {
             reject_incoming(call_id)?;
 
             Ok::<(), PjsuaError>(())
         })
-        .await
-        .unwrap()
+        .await?
     }
 }
 
-type CallStateReceiver = tokio::sync::mpsc::Receiver<(pjsua::pjsua_call_id, PjsipInvState)>;
+type CallStateReceiver = tokio::sync::mpsc::UnboundedReceiver<cb_user_data::OnStateChangedSendData>;
+type MediaEventReceiver = tokio::sync::mpsc::Receiver<cb_user_data::OnStateChangedSendData>;
+
+//media transport events come in bursts during ICE negotiation, older ones are of little use.
+const MEDIA_EVENT_CAPACITY: usize = 32;
 
 use super::pjmedia::pjmedia_port_audio_sink::*;
 use super::pjmedia::pjmedia_port_audio_stream::*;
 
+//informational events stay in media_event_rx, so that they can still be retrieved with
+//next_event.
 async fn await_call_state(
-    state_rx: &mut CallStateReceiver,
+    call_handle: &mut PjsuaCallHandle<'_>,
     state: PjsipInvState,
 ) -> Result<(), PjsuaError> {
-    eprintln!("Awaiting state: {:?}", state);
+    tracing::trace!(call_id = call_handle.call_id, ?state, "awaiting call state");
 
-    if let Some((_, state_recv)) = state_rx.recv().await {
-        if state_recv == state {
-            eprintln!("State received: {:?}", state);
-            return Ok(());
+    while let Some((_, call_event)) = call_handle.state_changed_rx.recv().await {
+        match call_event {
+            CallEvent::StateChanged(state_recv) if state_recv == state => {
+                tracing::trace!(call_id = call_handle.call_id, ?state, "call state reached");
+                return Ok(());
+            }
+            CallEvent::StateChanged(state_recv) => {
+                return Err(PjsuaError::InvalidState {
+                    operation: "await_call_state",
+                    expected: format!("{:?}", state),
+                    actual: format!("{:?}", state_recv),
+                })
+            }
+            CallEvent::Error(error) => return Err(error),
+            //only sent on the media event channel.
+            CallEvent::MediaTransportStateChanged(_) | CallEvent::MediaStateChanged(_) => {}
         }
     }
 
-    return Err(PjsuaError {
-        code: -1,
-        message: "Unexpected state".to_string(),
-    });
+    Err(PjsuaError::ChannelClosed {
+        operation: "await_call_state",
+    })
 }
 
 pub struct PjsuaCallSetup<'a> {
-    _account_id: pjsua::pjsua_acc_id,
+    account_id: pjsua::pjsua_acc_id,
     call_handle: PjsuaCallHandle<'a>,
     pjsua_instance_started: &'a pjsua_softphone_api::PjsuaInstanceStarted,
 }
@@ -250,7 +448,7 @@ impl<'a> PjsuaCallSetup<'a> {
 
         let call = Self {
             call_handle,
-            _account_id: incoming_call.account_id,
+            account_id: incoming_call.account_id,
             pjsua_instance_started: incoming_call.pjsua_instance_started,
         };
 
@@ -261,58 +459,129 @@ impl<'a> PjsuaCallSetup<'a> {
 
     pub async fn add(
         self,
-        sink: CustomSinkMediaPort<'a>,
-        stream: CustomStreamMediaPort<'a>,
-        mem_pool: &'a PjsuaMemoryPool,
+        sink: CustomSinkMediaPort,
+        stream: CustomStreamMediaPort,
     ) -> Result<PjsuaCall<'a>, PjsuaError> {
-        eprintln!("PjcuaCallSetup::add called");
-
         let mut call_handle = self.call_handle;
 
-        let sink_added = sink.add(mem_pool, &self.pjsua_instance_started)?;
-        let stream_added = stream.add(mem_pool, &self.pjsua_instance_started)?;
+        let call_media = CallMedia::add(sink, stream).await?;
 
         call_handle
             .call_media_data_tx
             .take()
-            .unwrap()
-            .send(CallMediaData {
-                sinks_slots: vec![CallMediaEntry {
-                    slot: sink_added.port_slot(),
-                }],
-                stream_slots: vec![CallMediaEntry {
-                    slot: stream_added.port_slot(),
-                }],
-            })
-            .unwrap();
-
-        eprintln!("Answering call...");
+            .and_then(|call_media_data_tx| call_media_data_tx.send(call_media.data()).ok())
+            .ok_or(PjsuaError::ChannelClosed {
+                operation: "PjsuaCallSetup::add",
+            })?;
 
         call_handle.answer(answer_code::Ok).await?;
 
-        eprintln!("Call answered");
+        tracing::info!(
+            acc_id = self.account_id,
+            call_id = call_handle.call_id,
+            "incoming call answered"
+        );
 
-        let mut pjsua_call = PjsuaCall::new(call_handle, sink_added, stream_added).await?;
+        let mut pjsua_call =
+            PjsuaCall::from_call_media(call_handle, call_media, self.pjsua_instance_started);
 
-        await_call_state(
-            &mut pjsua_call.call_handle.state_changed_rx,
-            PjsipInvState::Connecting,
-        )
-        .await?;
-        await_call_state(
-            &mut pjsua_call.call_handle.state_changed_rx,
-            PjsipInvState::Confirmed,
-        )
-        .await?;
+        await_call_state(&mut pjsua_call.call_handle, PjsipInvState::Connecting).await?;
+        await_call_state(&mut pjsua_call.call_handle, PjsipInvState::Confirmed).await?;
 
         Ok(pjsua_call)
     }
 }
 
+//intermediate states (Calling, Early, Connecting) are skipped.
+async fn await_call_confirmed(call_handle: &mut PjsuaCallHandle<'_>) -> Result<(), PjsuaError> {
+    while let Some((_, call_event)) = call_handle.state_changed_rx.recv().await {
+        match call_event {
+            CallEvent::StateChanged(PjsipInvState::Confirmed) => return Ok(()),
+            CallEvent::StateChanged(PjsipInvState::Disconnected) => {
+                return Err(PjsuaError::InvalidState {
+                    operation: "await_call_confirmed",
+                    expected: format!("{:?}", PjsipInvState::Confirmed),
+                    actual: format!("{:?}", PjsipInvState::Disconnected),
+                })
+            }
+            CallEvent::StateChanged(_) => {}
+            CallEvent::Error(error) => return Err(error),
+            //only sent on the media event channel.
+            CallEvent::MediaTransportStateChanged(_) | CallEvent::MediaStateChanged(_) => {}
+        }
+    }
+
+    Err(PjsuaError::ChannelClosed {
+        operation: "await_call_confirmed",
+    })
+}
+
+//every call gets its own pool for the conference bridge ports, released with the call.
+fn call_memory_pool() -> Result<PjsuaMemoryPool, PjsuaError> {
+    PjsuaMemoryPool::with_name("pjsua_call", DEFAULT_INIT_SIZE, DEFAULT_INCREMENT_SIZE).ok_or(
+        PjsuaError::Lifecycle {
+            operation: "pjsua_pool_create",
+            reason: "failed to create the call memory pool",
+        },
+    )
+}
+
+//the conference bridge ports of a call and the pool they are allocated from, created in one
+//pjsua worker command. Fields are dropped in declaration order, the ports before the pool.
+struct CallMedia {
+    sink: ConfBridgePort,
+    stream: ConfBridgePort,
+    mem_pool: PjsuaMemoryPool,
+}
+
+impl CallMedia {
+    async fn add(
+        sink: CustomSinkMediaPort,
+        stream: CustomStreamMediaPort,
+    ) -> Result<CallMedia, PjsuaError> {
+        let ports = unsafe { AssertSend::new((sink, stream)) };
+
+        let call_media = run_on_pjsua_thread(move || {
+            let (sink, stream) = ports.into_inner();
+
+            //declared before the ports, so that it is released after them on every error path.
+            let mem_pool = call_memory_pool()?;
+
+            let sink = sink.add_to_conf_bridge(&mem_pool)?;
+            let stream = stream.add_to_conf_bridge(&mem_pool)?;
+
+            let call_media = CallMedia {
+                sink,
+                stream,
+                mem_pool,
+            };
+
+            Ok::<_, PjsuaError>(unsafe { AssertSend::new(call_media) })
+        })
+        .await??;
+
+        Ok(call_media.into_inner())
+    }
+
+    fn data(&self) -> CallMediaData {
+        CallMediaData {
+            sinks_slots: vec![CallMediaEntry {
+                slot: self.sink.port_slot(),
+            }],
+            stream_slots: vec![CallMediaEntry {
+                slot: self.stream.port_slot(),
+            }],
+        }
+    }
+}
+
+//fields are dropped in declaration order: the media ports leave the conference bridge before
+//the call is hung up and before mem_pool, which they were allocated from, is released.
 pub struct PjsuaCall<'a> {
     media_sink: CustomSinkMediaPortAdded<'a>,
     media_stream: CustomStreamMediaPortAdded<'a>,
     call_handle: PjsuaCallHandle<'a>,
+    mem_pool: PjsuaMemoryPool,
 }
 
 impl<'a> PjsuaCall<'a> {
@@ -320,6 +589,7 @@ impl<'a> PjsuaCall<'a> {
         pjsua_call_setup: PjsuaCallHandle<'a>,
         media_sink: CustomSinkMediaPortAdded<'a>,
         media_stream: CustomStreamMediaPortAdded<'a>,
+        mem_pool: PjsuaMemoryPool,
     ) -> Result<PjsuaCall<'a>, PjsuaError> {
         let call_handle = pjsua_call_setup;
 
@@ -327,18 +597,81 @@ impl<'a> PjsuaCall<'a> {
             media_sink,
             media_stream,
             call_handle,
+            mem_pool,
         })
     }
 
-    pub async fn await_hangup(mut self) -> Result<(), PjsuaError> {
-        await_call_state(
-            &mut self.call_handle.state_changed_rx,
-            PjsipInvState::Disconnected,
+    fn from_call_media(
+        call_handle: PjsuaCallHandle<'a>,
+        call_media: CallMedia,
+        pjsua_instance_started: &'a pjsua_softphone_api::PjsuaInstanceStarted,
+    ) -> PjsuaCall<'a> {
+        let CallMedia {
+            sink,
+            stream,
+            mem_pool,
+        } = call_media;
+
+        Self {
+            media_sink: CustomSinkMediaPortAdded::new(sink, pjsua_instance_started),
+            media_stream: CustomStreamMediaPortAdded::new(stream, pjsua_instance_started),
+            call_handle,
+            mem_pool,
+        }
+    }
+
+    pub fn memory_pool_stats(&self) -> MemoryPoolStats {
+        self.mem_pool.stats()
+    }
+
+    //resolves once the remote party answered the call.
+    pub(crate) async fn make_call(
+        account_id: pjsua::pjsua_acc_id,
+        dst_uri: &str,
+        caller_id: Option<(CallerIdConfig, String)>,
+        sink: CustomSinkMediaPort,
+        stream: CustomStreamMediaPort,
+        pjsua_instance_started: &'a pjsua_softphone_api::PjsuaInstanceStarted,
+    ) -> Result<PjsuaCall<'a>, PjsuaError> {
+        let dst_uri = CString::new(dst_uri).map_err(|_| {
+            PjsuaError::invalid_config("make_call", "dst_uri contains an interior NUL byte")
+        })?;
+
+        let call_media = CallMedia::add(sink, stream).await?;
+
+        let call_handle = PjsuaCallHandle::make_call(
+            account_id,
+            dst_uri,
+            caller_id,
+            call_media.data(),
+            pjsua_instance_started,
         )
         .await?;
 
+        let mut pjsua_call =
+            PjsuaCall::from_call_media(call_handle, call_media, pjsua_instance_started);
+
+        await_call_confirmed(&mut pjsua_call.call_handle).await?;
+
+        Ok(pjsua_call)
+    }
+
+    pub async fn await_hangup(mut self) -> Result<(), PjsuaError> {
+        await_call_state(&mut self.call_handle, PjsipInvState::Disconnected).await?;
+
         Ok(())
     }
+
+    pub async fn next_event(&mut self) -> Option<CallEvent> {
+        self.call_handle.next_event().await
+    }
+
+    //None if no media of the call uses SRTP.
+    pub fn media_encryption(&self) -> Result<Option<MediaEncryption>, PjsuaError> {
+        let call_id = self.call_handle.call_id;
+
+        unsafe { run_on_pjsua_thread_blocking(|| pjsua_srtp::call_media_encryption(call_id)) }
+    }
 }
 
 impl<'a> PjsuaCall<'a> {
@@ -362,19 +695,72 @@ impl std::error::Error for RemoteAlreadyHangUpError {}
 
 pub(crate) mod cb_user_data {
     use super::tokio_oneshot;
+    use super::CallEvent;
     use super::CallMediaData;
-    use super::PjsipInvState;
-    use tokio::sync::mpsc::Sender;
+    use tokio::sync::mpsc::{Sender, UnboundedSender};
 
     #[allow(unused_parens)]
-    pub(crate) type OnStateChangedSendData = (pjsua::pjsua_call_id, PjsipInvState);
+    pub(crate) type OnStateChangedSendData = (pjsua::pjsua_call_id, CallEvent);
 
     pub struct StateChangedUserData {
-        pub(crate) on_state_changed_tx: Sender<OnStateChangedSendData>,
+        pub(crate) on_state_changed_tx: UnboundedSender<OnStateChangedSendData>,
+        //informational events, dropped by the callbacks when the consumer falls behind.
+        pub(crate) on_media_event_tx: Sender<OnStateChangedSendData>,
         pub(crate) call_media_data_rx: Option<tokio_oneshot::Receiver<CallMediaData>>,
     }
 }
 
+#[derive(Debug)]
+pub enum CallEvent {
+    StateChanged(PjsipInvState),
+    MediaTransportStateChanged(MediaTransportStateInfo),
+    MediaStateChanged(CallMediaStateInfo),
+    //a pjsip callback for this call failed, e.g. the call had to be hung up because no media
+    //was attached to it. await_hangup and call setup return the error.
+    Error(PjsuaError),
+}
+
+#[derive(Debug)]
+pub struct CallMediaStateInfo {
+    pub media_status: CallMediaStatus,
+    //None if the call media is not encrypted with SRTP.
+    pub encryption: Option<MediaEncryption>,
+}
+
+#[derive(Debug)]
+pub struct MediaTransportStateInfo {
+    pub media_index: u32,
+    pub state: MediaTransportState,
+    pub status: Result<(), PjsuaError>,
+    //None if the media transport is not an ICE transport.
+    pub ice_state: Option<IceState>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MediaTransportState {
+    Null,
+    Creating,
+    Idle,
+    Init,
+    Running,
+    Disabled,
+}
+
+impl TryFrom<u32> for MediaTransportState {
+    type Error = ();
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            pjsua::pjsua_med_tp_st_PJSUA_MED_TP_NULL => Ok(MediaTransportState::Null),
+            pjsua::pjsua_med_tp_st_PJSUA_MED_TP_CREATING => Ok(MediaTransportState::Creating),
+            pjsua::pjsua_med_tp_st_PJSUA_MED_TP_IDLE => Ok(MediaTransportState::Idle),
+            pjsua::pjsua_med_tp_st_PJSUA_MED_TP_INIT => Ok(MediaTransportState::Init),
+            pjsua::pjsua_med_tp_st_PJSUA_MED_TP_RUNNING => Ok(MediaTransportState::Running),
+            pjsua::pjsua_med_tp_st_PJSUA_MED_TP_DISABLED => Ok(MediaTransportState::Disabled),
+            _ => Err(()),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum PjsipInvState {
     Null,
@@ -402,7 +788,7 @@ impl TryFrom<u32> for PjsipInvState {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CallMediaStatus {
     None,
     Active,
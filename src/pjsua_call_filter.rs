@@ -0,0 +1,438 @@
+use crate::pjsua_config::PjsuaConfigError;
+
+use std::ffi::CStr;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+//glob pattern, '*' matches any sequence of characters and '?' matches a single character.
+//matching is case insensitive, as are most parts of SIP URIs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobPattern {
+    pattern: Vec<char>,
+}
+
+impl GlobPattern {
+    pub fn new(pattern: &str) -> Self {
+        GlobPattern {
+            pattern: pattern.to_lowercase().chars().collect(),
+        }
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        let value: Vec<char> = value.to_lowercase().chars().collect();
+
+        let (mut p, mut v) = (0, 0);
+        let mut backtrack: Option<(usize, usize)> = None;
+
+        while v < value.len() {
+            match self.pattern.get(p) {
+                Some('*') => {
+                    backtrack = Some((p, v));
+                    p += 1;
+                }
+                Some(&c) if c == '?' || c == value[v] => {
+                    p += 1;
+                    v += 1;
+                }
+                _ => match backtrack {
+                    Some((star_p, star_v)) => {
+                        p = star_p + 1;
+                        v = star_v + 1;
+                        backtrack = Some((star_p, star_v + 1));
+                    }
+                    None => return false,
+                },
+            }
+        }
+
+        self.pattern[p..].iter().all(|&c| c == '*')
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCidrError(pub String);
+
+impl std::fmt::Display for InvalidCidrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid CIDR: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidCidrError {}
+
+impl FromStr for IpCidr {
+    type Err = InvalidCidrError;
+
+    //accepts "10.0.0.0/8", "2001:db8::/32" or a single address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || InvalidCidrError(s.to_string());
+
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+
+        let addr = IpAddr::from_str(addr).map_err(|_| error())?;
+
+        let max_prefix_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => u8::from_str(prefix_len).map_err(|_| error())?,
+            None => max_prefix_len,
+        };
+
+        if prefix_len > max_prefix_len {
+            return Err(error());
+        }
+
+        Ok(IpCidr { addr, prefix_len })
+    }
+}
+
+impl IpCidr {
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        fn prefix_eq(a: &[u8], b: &[u8], prefix_len: usize) -> bool {
+            let (bytes, bits) = (prefix_len / 8, prefix_len % 8);
+
+            if a[..bytes] != b[..bytes] {
+                return false;
+            }
+
+            let mask = match bits {
+                0 => return true,
+                bits => 0xffu8 << (8 - bits),
+            };
+
+            a[bytes] & mask == b[bytes] & mask
+        }
+
+        let prefix_len = self.prefix_len as usize;
+
+        match (self.addr, addr) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                prefix_eq(&net.octets(), &addr.octets(), prefix_len)
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                prefix_eq(&net.octets(), &addr.octets(), prefix_len)
+            }
+            (IpAddr::V4(net), IpAddr::V6(addr)) => match addr.to_ipv4_mapped() {
+                Some(addr) => prefix_eq(&net.octets(), &addr.octets(), prefix_len),
+                None => false,
+            },
+            (IpAddr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CallMatch {
+    //URI of the From header, without the display name, e.g. "sip:*@trusted.example.com".
+    CallerUri(GlobPattern),
+    //address the INVITE was received from.
+    SourceIp(IpCidr),
+    //user part of the To header URI.
+    ToUser(GlobPattern),
+    //value of the first header with the given name. Calls without the header never match.
+    Header { name: String, value: GlobPattern },
+}
+
+//denied calls, and calls matching no allow rule (if there are any), are rejected with
+//reject_code inside on_incoming_call and never reach IncomingCallReceiver.
+#[derive(Debug, Clone)]
+pub struct CallFilter {
+    pub allow: Vec<CallMatch>,
+    pub deny: Vec<CallMatch>,
+    reject_code: u16,
+}
+
+impl Default for CallFilter {
+    fn default() -> Self {
+        CallFilter {
+            allow: Vec::new(),
+            deny: Vec::new(),
+            reject_code: 403,
+        }
+    }
+}
+
+impl CallFilter {
+    //reject_code must be a final error response, 403 is used by default.
+    pub fn with_reject_code(mut self, reject_code: u16) -> Result<Self, PjsuaConfigError> {
+        if !(400..=699).contains(&reject_code) {
+            return Err(PjsuaConfigError::InvalidValue(
+                "reject_code",
+                format!("{} is not a SIP error response code (400-699)", reject_code),
+            ));
+        }
+
+        self.reject_code = reject_code;
+
+        Ok(self)
+    }
+
+    pub fn reject_code(&self) -> u16 {
+        self.reject_code
+    }
+
+    //returns the SIP status code to reject the call with, or None if the call is accepted.
+    pub(crate) unsafe fn check(&self, rx_data: &pjsua::pjsip_rx_data) -> Option<u16> {
+        self.check_with(|rule| rule_matches(rule, rx_data))
+    }
+
+    //a matching deny rule rejects the call even if an allow rule matches as well.
+    fn check_with(&self, rule_matches: impl Fn(&CallMatch) -> bool) -> Option<u16> {
+        if self.allow.is_empty() && self.deny.is_empty() {
+            return None;
+        }
+
+        let rejected = self.deny.iter().any(&rule_matches)
+            || (!self.allow.is_empty() && !self.allow.iter().any(&rule_matches));
+
+        match rejected {
+            true => Some(self.reject_code),
+            false => None,
+        }
+    }
+}
+
+unsafe fn rule_matches(rule: &CallMatch, rx_data: &pjsua::pjsip_rx_data) -> bool {
+    match rule {
+        CallMatch::CallerUri(pattern) => match from_uri(rx_data) {
+            Some(uri) => pattern.matches(&uri),
+            None => false,
+        },
+        CallMatch::SourceIp(cidr) => match source_ip(rx_data) {
+            Some(addr) => cidr.contains(&addr),
+            None => false,
+        },
+        CallMatch::ToUser(pattern) => match to_uri(rx_data).as_deref().and_then(uri_user) {
+            Some(user) => pattern.matches(user),
+            None => false,
+        },
+        CallMatch::Header { name, value } => match header_value(rx_data, name) {
+            Some(header_value) => value.matches(&header_value),
+            None => false,
+        },
+    }
+}
+
+const URI_BUFFER_SIZE: usize = 512;
+
+unsafe fn print_uri(uri: *mut pjsua::pjsip_uri) -> Option<String> {
+    let uri = uri.as_ref()?;
+
+    //pjsip_uri_get_uri and pjsip_uri_print are inline functions, so the vptr is used directly.
+    //p_get_uri strips the display name of a name-addr.
+    let vptr = uri.vptr.as_ref()?;
+    let uri =
+        (vptr.p_get_uri?)(uri as *const _ as *mut std::ffi::c_void) as *const pjsua::pjsip_uri;
+    let print = (*uri.as_ref()?.vptr).p_print?;
+
+    let mut buffer = [0u8; URI_BUFFER_SIZE];
+
+    let len = print(
+        pjsua::pjsip_uri_context_e_PJSIP_URI_IN_FROMTO_HDR,
+        uri as *const std::ffi::c_void,
+        buffer.as_mut_ptr() as *mut std::os::raw::c_char,
+        buffer.len() as pjsua::pj_size_t,
+    );
+
+    match len {
+        len if len < 0 => None,
+        len => Some(String::from_utf8_lossy(&buffer[..len as usize]).into_owned()),
+    }
+}
+
+unsafe fn from_uri(rx_data: &pjsua::pjsip_rx_data) -> Option<String> {
+    print_uri(rx_data.msg_info.from.as_ref()?.uri)
+}
+
+unsafe fn to_uri(rx_data: &pjsua::pjsip_rx_data) -> Option<String> {
+    print_uri(rx_data.msg_info.to.as_ref()?.uri)
+}
+
+//"sip:alice@example.com;transport=tcp" -> "alice"
+fn uri_user(uri: &str) -> Option<&str> {
+    let (_, rest) = uri.split_once(':')?;
+    let (user, _) = rest.split_once('@')?;
+
+    Some(user.split(';').next().unwrap_or(user))
+}
+
+unsafe fn source_ip(rx_data: &pjsua::pjsip_rx_data) -> Option<IpAddr> {
+    let src_name = CStr::from_ptr(rx_data.pkt_info.src_name.as_ptr());
+
+    IpAddr::from_str(src_name.to_str().ok()?).ok()
+}
+
+unsafe fn header_value(rx_data: &pjsua::pjsip_rx_data, name: &str) -> Option<String> {
+    let name = pjsua::pj_str_t {
+        ptr: name.as_ptr() as *mut std::os::raw::c_char,
+        slen: name.len() as pjsua::pj_ssize_t,
+    };
+
+    let header = pjsua::pjsip_msg_find_hdr_by_name(rx_data.msg_info.msg, &name, std::ptr::null());
+
+    if header.is_null() {
+        return None;
+    }
+
+    let mut buffer = [0u8; URI_BUFFER_SIZE];
+
+    let len = pjsua::pjsip_hdr_print_on(
+        header as *mut std::ffi::c_void,
+        buffer.as_mut_ptr() as *mut std::os::raw::c_char,
+        buffer.len() as pjsua::pj_size_t,
+    );
+
+    if len < 0 {
+        return None;
+    }
+
+    //printed as "Name: value"
+    let printed = String::from_utf8_lossy(&buffer[..len as usize]).into_owned();
+
+    printed
+        .split_once(':')
+        .map(|(_, value)| value.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(cidr: &str) -> IpCidr {
+        cidr.parse().unwrap()
+    }
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn glob_backtracks_over_stars() {
+        let pattern = GlobPattern::new("a*b*c");
+
+        assert!(pattern.matches("abc"));
+        assert!(pattern.matches("axxbyyc"));
+        assert!(pattern.matches("abbbc"));
+        assert!(pattern.matches("abcbc"));
+        assert!(pattern.matches("ABC"));
+
+        assert!(!pattern.matches("ab"));
+        assert!(!pattern.matches("acb"));
+        assert!(!pattern.matches("abcx"));
+        assert!(!pattern.matches("xabc"));
+    }
+
+    #[test]
+    fn glob_question_mark_and_empty() {
+        assert!(GlobPattern::new("sip:?ob@*").matches("sip:bob@example.com"));
+        assert!(!GlobPattern::new("sip:?ob@*").matches("sip:ob@example.com"));
+
+        assert!(GlobPattern::new("*").matches(""));
+        assert!(GlobPattern::new("").matches(""));
+        assert!(!GlobPattern::new("").matches("a"));
+    }
+
+    #[test]
+    fn cidr_prefix_bounds() {
+        assert!(cidr("0.0.0.0/0").contains(&ip("203.0.113.7")));
+
+        assert!(cidr("192.0.2.1/32").contains(&ip("192.0.2.1")));
+        assert!(!cidr("192.0.2.1/32").contains(&ip("192.0.2.2")));
+        assert!(cidr("192.0.2.1").contains(&ip("192.0.2.1")));
+
+        assert!(cidr("10.0.0.0/9").contains(&ip("10.127.0.1")));
+        assert!(!cidr("10.0.0.0/9").contains(&ip("10.128.0.1")));
+
+        assert!(cidr("::/0").contains(&ip("2001:db8::1")));
+        assert!(cidr("2001:db8::1/128").contains(&ip("2001:db8::1")));
+        assert!(!cidr("2001:db8::1/128").contains(&ip("2001:db8::2")));
+
+        assert!("192.0.2.0/33".parse::<IpCidr>().is_err());
+        assert!("2001:db8::/129".parse::<IpCidr>().is_err());
+        assert!("192.0.2.0/".parse::<IpCidr>().is_err());
+    }
+
+    #[test]
+    fn cidr_ipv4_mapped_source() {
+        assert!(cidr("10.0.0.0/8").contains(&ip("::ffff:10.1.2.3")));
+        assert!(!cidr("10.0.0.0/8").contains(&ip("::ffff:11.1.2.3")));
+        assert!(cidr("0.0.0.0/0").contains(&ip("::ffff:203.0.113.7")));
+
+        //only mapped addresses are compared with IPv4 networks.
+        assert!(!cidr("0.0.0.0/0").contains(&ip("2001:db8::1")));
+        assert!(!cidr("::/0").contains(&ip("10.1.2.3")));
+    }
+
+    #[test]
+    fn uri_user_part() {
+        assert_eq!(uri_user("sip:alice@example.com"), Some("alice"));
+        assert_eq!(uri_user("sips:alice@[::1]:5061"), Some("alice"));
+        assert_eq!(uri_user("sip:alice;ext=1@example.com"), Some("alice"));
+        assert_eq!(uri_user("sip:example.com"), None);
+        assert_eq!(uri_user("alice@example.com"), None);
+    }
+
+    #[test]
+    fn deny_overrides_allow() {
+        let filter = CallFilter {
+            allow: vec![CallMatch::CallerUri(GlobPattern::new("sip:*@example.com"))],
+            deny: vec![CallMatch::CallerUri(GlobPattern::new("sip:mallory@*"))],
+            ..CallFilter::default()
+        };
+
+        let check = |caller: &str| {
+            filter.check_with(|rule| match rule {
+                CallMatch::CallerUri(pattern) => pattern.matches(caller),
+                _ => false,
+            })
+        };
+
+        assert_eq!(check("sip:alice@example.com"), None);
+        assert_eq!(check("sip:mallory@example.com"), Some(403));
+        assert_eq!(check("sip:alice@example.org"), Some(403));
+    }
+
+    #[test]
+    fn no_rules_accept_every_call() {
+        assert_eq!(CallFilter::default().check_with(|_| false), None);
+
+        let deny_only = CallFilter {
+            deny: vec![CallMatch::ToUser(GlobPattern::new("admin"))],
+            ..CallFilter::default()
+        };
+
+        assert_eq!(deny_only.check_with(|_| false), None);
+        assert_eq!(deny_only.check_with(|_| true), Some(403));
+    }
+
+    #[test]
+    fn reject_code_bounds() {
+        assert!(CallFilter::default().with_reject_code(399).is_err());
+        assert!(CallFilter::default().with_reject_code(700).is_err());
+
+        let filter = CallFilter::default().with_reject_code(400).unwrap();
+        assert_eq!(filter.reject_code(), 400);
+
+        let filter = CallFilter::default().with_reject_code(699).unwrap();
+        assert_eq!(filter.reject_code(), 699);
+        assert_eq!(filter.check_with(|_| false), None);
+
+        let filter = CallFilter {
+            deny: vec![CallMatch::SourceIp(cidr("0.0.0.0/0"))],
+            ..filter
+        };
+        assert_eq!(filter.check_with(|_| true), Some(699));
+    }
+}
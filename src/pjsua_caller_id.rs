@@ -0,0 +1,170 @@
+use crate::error::PjsuaError;
+use crate::pjsua_memory_pool::PjsuaMemoryPool;
+use crate::tokio_utils::run_on_pjsua_thread_blocking;
+
+use std::ffi::CString;
+use std::mem::MaybeUninit;
+
+const ANONYMOUS_FROM: &str = "\"Anonymous\" <sip:anonymous@anonymous.invalid>";
+
+//identity presented on outgoing calls. Values of the identity headers are name-addrs,
+//e.g. "\"Alice\" <sip:+15551234567@carrier.example.com>".
+#[derive(Debug, Clone, Default)]
+pub struct CallerIdConfig {
+    //overrides the display name of the account id in the From header.
+    pub display_name: Option<String>,
+    //RFC 3323: anonymous From header and "Privacy: id". Unless set otherwise, the account id
+    //is sent in P-Preferred-Identity so that the trusted network can still identify the caller.
+    pub anonymous: bool,
+    pub asserted_identity: Option<String>,
+    pub preferred_identity: Option<String>,
+}
+
+//pjsua_msg_data for pjsua_call_make_call, headers are allocated in mem_pool.
+pub(crate) struct CallerIdMsgData {
+    msg_data: Box<pjsua::pjsua_msg_data>,
+    _local_uri: Option<CString>,
+    _mem_pool: PjsuaMemoryPool,
+}
+
+impl AsRef<pjsua::pjsua_msg_data> for CallerIdMsgData {
+    fn as_ref(&self) -> &pjsua::pjsua_msg_data {
+        &self.msg_data
+    }
+}
+
+fn invalid_caller_id(field: &str) -> PjsuaError {
+    PjsuaError::invalid_config(
+        "caller_id",
+        format!("{} contains an interior NUL byte", field),
+    )
+}
+
+fn pj_str_of(string: &str) -> pjsua::pj_str_t {
+    pjsua::pj_str_t {
+        ptr: string.as_ptr() as *mut std::os::raw::c_char,
+        slen: string.len() as pjsua::pj_ssize_t,
+    }
+}
+
+//"sip:alice@example.com" or "\"Bob\" <sip:alice@example.com>" -> "sip:alice@example.com"
+fn strip_display_name(uri: &str) -> &str {
+    match (uri.find('<'), uri.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &uri[start + 1..end],
+        _ => uri,
+    }
+}
+
+impl CallerIdConfig {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.display_name.is_none()
+            && !self.anonymous
+            && self.asserted_identity.is_none()
+            && self.preferred_identity.is_none()
+    }
+
+    fn local_uri(&self, account_id: &str) -> Option<String> {
+        match (self.anonymous, &self.display_name) {
+            (true, _) => Some(ANONYMOUS_FROM.to_string()),
+            (false, Some(display_name)) => {
+                let display_name = display_name.replace('\\', "\\\\").replace('"', "\\\"");
+
+                Some(format!(
+                    "\"{}\" <{}>",
+                    display_name,
+                    strip_display_name(account_id)
+                ))
+            }
+            (false, None) => None,
+        }
+    }
+
+    fn headers(&self, account_id: &str) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+
+        if self.anonymous {
+            headers.push(("Privacy", "id".to_string()));
+        }
+
+        if let Some(asserted_identity) = &self.asserted_identity {
+            headers.push(("P-Asserted-Identity", asserted_identity.clone()));
+        }
+
+        match (&self.preferred_identity, self.anonymous) {
+            (Some(preferred_identity), _) => {
+                headers.push(("P-Preferred-Identity", preferred_identity.clone()))
+            }
+            (None, true) if self.asserted_identity.is_none() => headers.push((
+                "P-Preferred-Identity",
+                format!("<{}>", strip_display_name(account_id)),
+            )),
+            (None, _) => {}
+        }
+
+        headers
+    }
+
+    pub(crate) fn to_msg_data(&self, account_id: &str) -> Result<CallerIdMsgData, PjsuaError> {
+        let local_uri = match self.local_uri(account_id) {
+            Some(local_uri) => {
+                Some(CString::new(local_uri).map_err(|_| invalid_caller_id("display_name"))?)
+            }
+            None => None,
+        };
+
+        let headers = self.headers(account_id);
+
+        if let Some((name, _)) = headers.iter().find(|(_, value)| value.contains('\0')) {
+            return Err(invalid_caller_id(name));
+        }
+
+        let mem_pool = PjsuaMemoryPool::new(1024, 1024).ok_or(PjsuaError::Lifecycle {
+            operation: "caller_id_msg_data",
+            reason: "failed to create memory pool",
+        })?;
+
+        let mut msg_data =
+            Box::new(unsafe { MaybeUninit::<pjsua::pjsua_msg_data>::zeroed().assume_init() });
+
+        //the headers are created in pjsua's pool, that has to happen on the pjsua worker.
+        unsafe {
+            run_on_pjsua_thread_blocking(|| {
+                pjsua::pjsua_msg_data_init(msg_data.as_mut());
+
+                if let Some(local_uri) = &local_uri {
+                    msg_data.local_uri = pjsua::pj_str(local_uri.as_ptr() as *mut i8);
+                }
+
+                for (name, value) in &headers {
+                    //pjsip_generic_string_hdr_create copies name and value into the pool.
+                    let header = pjsua::pjsip_generic_string_hdr_create(
+                        mem_pool.raw_handle(),
+                        &pj_str_of(name),
+                        &pj_str_of(value),
+                    );
+
+                    if header.is_null() {
+                        return Err(PjsuaError::Lifecycle {
+                            operation: "pjsip_generic_string_hdr_create",
+                            reason: "failed to allocate header",
+                        });
+                    }
+
+                    //pj_list_push_back is inline, pj_list_insert_before is its exported equivalent.
+                    pjsua::pj_list_insert_before(
+                        &mut msg_data.hdr_list as *mut _ as *mut std::ffi::c_void,
+                        header as *mut std::ffi::c_void,
+                    );
+                }
+
+                Ok(())
+            })??;
+        }
+
+        Ok(CallerIdMsgData {
+            msg_data,
+            _local_uri: local_uri,
+            _mem_pool: mem_pool,
+        })
+    }
+}
@@ -1,77 +1,259 @@
 use crate::{
-    error::ffi_assert_res,
-    error::get_error_as_result,
-    ffi_assert,
+    error::{catch_callback_panic, get_error_as_result, PjsuaError},
+    pjmedia::pjmedia_api,
     pjsua_account_config::cb_user_data::{AccountConfigUserData, OnIncomingCallSendData},
     pjsua_call::cb_user_data::StateChangedUserData,
-    pjsua_call::PjsipInvState,
+    pjsua_call::{CallEvent, MediaTransportState, MediaTransportStateInfo, PjsipInvState},
+    pjsua_ice,
+    pjsua_log::{self, LogLevel},
+    pjsua_nat, pjsua_srtp, transport,
 };
 
+use std::ffi::CString;
 use std::mem::MaybeUninit;
+use tokio::sync::mpsc::error::TrySendError;
 
-pub unsafe extern "C" fn on_incoming_call(
-    acc_id: pjsua::pjsua_acc_id,
+//final response for calls that can't be handed to the application.
+const SIP_SC_BUSY_HERE: u32 = 486;
+const SIP_SC_TEMPORARILY_UNAVAILABLE: u32 = 480;
+const SIP_SC_INTERNAL_SERVER_ERROR: u32 = 500;
+
+unsafe fn hangup_call_with(call_id: pjsua::pjsua_call_id, code: u32) {
+    let status = get_error_as_result(
+        "pjsua_call_hangup",
+        pjsua::pjsua_call_hangup(call_id, code, std::ptr::null(), std::ptr::null()),
+    );
+
+    if let Err(e) = status {
+        tracing::warn!(call_id, code, error = %e, "failed to hang up call");
+    }
+}
+
+//calls without a PjsuaCallHandle, e.g. rejected by the call filter, have no user data and
+//their events are skipped.
+unsafe fn send_call_event(call_id: pjsua::pjsua_call_id, call_event: CallEvent) {
+    let Some(state_changed_user_data) =
+        (pjsua::pjsua_call_get_user_data(call_id) as *const StateChangedUserData).as_ref()
+    else {
+        return;
+    };
+
+    if let Err(e) = state_changed_user_data
+        .on_state_changed_tx
+        .send((call_id, call_event))
+    {
+        let (_, call_event) = e.0;
+        tracing::debug!(
+            call_id,
+            ?call_event,
+            "call handle is gone, dropping call event"
+        );
+    }
+}
+
+//informational events go through their own bounded channel, a slow consumer only loses these
+//and never delays state changes.
+unsafe fn send_media_event(call_id: pjsua::pjsua_call_id, call_event: CallEvent) {
+    let Some(state_changed_user_data) =
+        (pjsua::pjsua_call_get_user_data(call_id) as *const StateChangedUserData).as_ref()
+    else {
+        return;
+    };
+
+    match state_changed_user_data
+        .on_media_event_tx
+        .try_send((call_id, call_event))
+    {
+        Ok(()) => {}
+        Err(TrySendError::Full((_, call_event))) => {
+            tracing::warn!(
+                call_id,
+                ?call_event,
+                "media event channel is full, dropping event"
+            );
+        }
+        Err(TrySendError::Closed((_, call_event))) => {
+            tracing::debug!(
+                call_id,
+                ?call_event,
+                "call handle is gone, dropping media event"
+            );
+        }
+    }
+}
+
+//a failing or panicking call callback ends up as CallEvent::Error on the call it belongs to.
+unsafe fn run_call_callback(
+    callback: &'static str,
     call_id: pjsua::pjsua_call_id,
-    rx_data: *mut pjsua::pjsip_rx_data,
+    f: impl FnOnce() -> Result<(), PjsuaError>,
 ) {
-    eprintln!("on_incoming_call callback...");
-    ffi_assert!(!rx_data.is_null(), "rx_data musn't be null!");
+    let error = match catch_callback_panic(callback, f) {
+        Ok(Ok(())) => return,
+        Ok(Err(error)) | Err(error) => error,
+    };
 
-    let rx_data = rx_data.as_mut().unwrap();
-    let transport_info = rx_data.tp_info;
-    let _transport = transport_info.transport.as_ref().unwrap();
+    tracing::warn!(call_id, callback, error = %error, "call callback failed");
 
-    let account_user_data = pjsua::pjsua_acc_get_user_data(acc_id) as *const AccountConfigUserData;
+    send_call_event(call_id, CallEvent::Error(error));
+}
+
+fn unknown_value(operation: &'static str, expected: &str, actual: u32) -> PjsuaError {
+    PjsuaError::InvalidState {
+        operation,
+        expected: format!("a known {}", expected),
+        actual: actual.to_string(),
+    }
+}
 
-    //user data should be valid, allocated ptr here due to ffi_assert!
-    //also, since pjsua_acc_del is called on Drop in AccountConfigAdded, where this buffer is allocated,
+//returns the final response code if the call can't be handed to next_call.
+unsafe fn dispatch_incoming_call(
+    acc_id: pjsua::pjsua_acc_id,
+    call_id: pjsua::pjsua_call_id,
+    rx_data: *mut pjsua::pjsip_rx_data,
+) -> Option<u32> {
+    let Some(rx_data) = rx_data.as_ref() else {
+        tracing::warn!(acc_id, call_id, "on_incoming_call: rx_data is null");
+        return Some(SIP_SC_INTERNAL_SERVER_ERROR);
+    };
+
+    //since pjsua_acc_del is called on Drop in AccountConfigAdded, where this buffer is allocated,
+    //a non null user data is valid here.
     //also not that this value is not stored in reference/box due to aliasing invariants of Rust.
+    let account_user_data = pjsua::pjsua_acc_get_user_data(acc_id) as *const AccountConfigUserData;
 
-    ffi_assert!(
-        !account_user_data.is_null(),
-        "on_incoming_call_tx channel is closed!"
-    );
+    let Some(account_user_data) = account_user_data.as_ref() else {
+        tracing::warn!(
+            acc_id,
+            call_id,
+            "on_incoming_call: account has no user data"
+        );
+        return Some(SIP_SC_TEMPORARILY_UNAVAILABLE);
+    };
+
+    let reject_code = match account_user_data.call_filter.read() {
+        Ok(call_filter) => call_filter.check(rx_data),
+        Err(poisoned) => poisoned.into_inner().check(rx_data),
+    };
+
+    if let Some(reject_code) = reject_code {
+        tracing::info!(
+            acc_id,
+            call_id,
+            reject_code,
+            "incoming call rejected by call filter"
+        );
+
+        return Some(reject_code as u32);
+    }
 
-    let incoming_call_tx = &(*account_user_data).on_incoming_call_tx;
     let send_data: OnIncomingCallSendData = (acc_id, call_id);
-    incoming_call_tx
-        .try_send(send_data)
-        .expect("channel should not be closed at that point!");
 
-    eprintln!("on_incoming_call callback returned");
+    match account_user_data.on_incoming_call_tx.try_send(send_data) {
+        Ok(()) => None,
+        Err(TrySendError::Full(_)) => {
+            tracing::warn!(
+                acc_id,
+                call_id,
+                "incoming call queue is full, rejecting call"
+            );
+            Some(SIP_SC_BUSY_HERE)
+        }
+        Err(TrySendError::Closed(_)) => {
+            tracing::warn!(
+                acc_id,
+                call_id,
+                "incoming call receiver is gone, rejecting call"
+            );
+            Some(SIP_SC_TEMPORARILY_UNAVAILABLE)
+        }
+    }
+}
+
+pub unsafe extern "C" fn on_incoming_call(
+    acc_id: pjsua::pjsua_acc_id,
+    call_id: pjsua::pjsua_call_id,
+    rx_data: *mut pjsua::pjsip_rx_data,
+) {
+    tracing::debug!(acc_id, call_id, "on_incoming_call");
+
+    let reject_code = catch_callback_panic("on_incoming_call", || {
+        dispatch_incoming_call(acc_id, call_id, rx_data)
+    })
+    .unwrap_or(Some(SIP_SC_INTERNAL_SERVER_ERROR));
+
+    if let Some(reject_code) = reject_code {
+        hangup_call_with(call_id, reject_code);
+    }
 }
 
 pub unsafe extern "C" fn on_call_state(
     call_id: pjsua::pjsua_call_id,
     _pjsip_event: *mut pjsua::pjsip_event,
 ) {
-    //    ffi_assert!(!pjsip_event.is_null(), "pjsip_event musn't be null!");
+    use super::pjsua_call;
 
     //state_changed_user_data may me null when the on_incoming_call is called, but no
     //OnIncomingCall instance is created.
+    if pjsua::pjsua_call_get_user_data(call_id).is_null() {
+        return;
+    }
 
-    if let Some(state_changed_user_data) =
-        (pjsua::pjsua_call_get_user_data(call_id) as *mut StateChangedUserData).as_mut()
-    {
-        let mut info = MaybeUninit::<pjsua::pjsua_call_info>::zeroed().assume_init();
-        pjsua::pjsua_call_get_info(call_id, &mut info);
+    run_call_callback("on_call_state", call_id, || {
+        let call_info = pjsua_call::get_call_info(call_id)?;
 
-        let state: Result<PjsipInvState, ()> = info.state.try_into();
+        let state = PjsipInvState::try_from(call_info.state)
+            .map_err(|_| unknown_value("on_call_state", "pjsip_inv_state", call_info.state))?;
 
-        eprintln!("on_call_state callback: {:?}", state);
+        tracing::debug!(call_id, ?state, "on_call_state");
 
-        let state = ffi_assert_res(state);
+        send_call_event(call_id, CallEvent::StateChanged(state));
 
-        let res = (*state_changed_user_data)
-            .on_state_changed_tx
-            .try_send((call_id, state));
+        Ok(())
+    });
+}
 
-        ffi_assert_res(res);
-    }
+unsafe extern "C" fn on_call_media_transport_state(
+    call_id: pjsua::pjsua_call_id,
+    info: *const pjsua::pjsua_med_tp_state_info,
+) {
+    let Some(info) = info.as_ref() else {
+        return;
+    };
+
+    run_call_callback("on_call_media_transport_state", call_id, || {
+        let state = match MediaTransportState::try_from(info.state as u32) {
+            Ok(state) => state,
+            Err(_) => {
+                tracing::warn!(call_id, state = info.state, "unknown media transport state");
+                return Ok(());
+            }
+        };
+
+        let transport_state_info = MediaTransportStateInfo {
+            media_index: info.med_idx,
+            state,
+            status: get_error_as_result("on_call_media_transport_state", info.status),
+            ice_state: pjsua_ice::call_ice_state(call_id, info.med_idx),
+        };
+
+        tracing::debug!(call_id, info = ?transport_state_info, "on_call_media_transport_state");
+
+        send_media_event(
+            call_id,
+            CallEvent::MediaTransportStateChanged(transport_state_info),
+        );
+
+        Ok(())
+    });
 }
 
 unsafe extern "C" fn on_media_event(event: *mut pjsua::pjmedia_event) {
-    eprintln!("on_media_event callback. type: {:?}", (*event).type_);
+    let _ = catch_callback_panic("on_media_event", || {
+        if let Some(event) = event.as_ref() {
+            tracing::trace!(event_type = event.type_, "on_media_event");
+        }
+    });
 }
 
 unsafe extern "C" fn on_create_media_transport(
@@ -80,70 +262,136 @@ unsafe extern "C" fn on_create_media_transport(
     base_tp: *mut pjsua::pjmedia_transport,
     flags: ::std::os::raw::c_uint,
 ) -> *mut pjsua::pjmedia_transport {
-    eprintln!(
-        "on_create_media_transport callback. call_id: {:?}, media_idx: {:?}, flags: {:?}",
-        call_id, media_idx, flags
-    );
+    catch_callback_panic("on_create_media_transport", || {
+        tracing::debug!(call_id, media_idx, flags, "on_create_media_transport");
+
+        base_tp
+    })
+    .unwrap_or(base_tp)
+}
 
-    base_tp
+unsafe fn connect_slots(stream: i32, sink: i32) -> Result<(), PjsuaError> {
+    get_error_as_result(
+        "pjsua_conf_connect",
+        pjsua::pjsua_conf_connect(stream, sink),
+    )?;
+
+    tracing::debug!(stream, sink, "connected conf bridge slots");
+
+    Ok(())
 }
 
-unsafe fn connect_slots(stream: i32, sink: i32) {
-    eprintln!("Connecting {:?} to {:?}...", stream, sink);
+//connects the ports handed over by PjsuaCallSetup::add or make_call, once per call.
+//A confirmed call without media data is hung up instead of staying up silently.
+unsafe fn connect_call_media(
+    call_id: pjsua::pjsua_call_id,
+    state_changed_user_data: &mut StateChangedUserData,
+) -> Result<(), PjsuaError> {
+    use super::pjsua_call;
+
+    let Some(mut call_media_data_rx) = state_changed_user_data.call_media_data_rx.take() else {
+        return Ok(());
+    };
+
+    let call_media_data = match call_media_data_rx.try_recv() {
+        Ok(call_media_data) => call_media_data,
+        Err(_) => {
+            tracing::error!(call_id, "no call media data received, hanging up");
+            hangup_call_with(call_id, SIP_SC_INTERNAL_SERVER_ERROR);
+
+            return Err(PjsuaError::Lifecycle {
+                operation: "on_call_media_state",
+                reason: "no call media data received, call was hung up",
+            });
+        }
+    };
+
+    let call_conf_port = pjsua_call::get_call_conf_port(call_id)?;
 
-    let status = get_error_as_result(pjsua::pjsua_conf_connect(stream, sink));
+    for entry in call_media_data.sinks_slots.iter() {
+        connect_slots(call_conf_port, entry.slot)?;
+    }
 
-    ffi_assert_res(status);
+    for entry in call_media_data.stream_slots.iter() {
+        connect_slots(entry.slot, call_conf_port)?;
+    }
 
-    eprintln!("connected conf bridge slots {:?} -> {:?}", stream, sink)
+    Ok(())
 }
 
 unsafe extern "C" fn on_call_media_state(call_id: pjsua::pjsua_call_id) {
     use super::pjsua_call;
 
-    let state_changed_user_data =
-        pjsua::pjsua_call_get_user_data(call_id) as *mut StateChangedUserData;
+    run_call_callback("on_call_media_state", call_id, || {
+        let call_info = pjsua_call::get_call_info(call_id)?;
 
-    let call_info = ffi_assert_res(pjsua_call::get_call_info(call_id));
+        let media_status =
+            pjsua_call::CallMediaStatus::try_from(call_info.media_status).map_err(|_| {
+                unknown_value(
+                    "on_call_media_state",
+                    "pjsua_call_media_status",
+                    call_info.media_status,
+                )
+            })?;
+        let call_state = PjsipInvState::try_from(call_info.state).map_err(|_| {
+            unknown_value("on_call_media_state", "pjsip_inv_state", call_info.state)
+        })?;
 
-    let media_status: pjsua_call::CallMediaStatus =
-        ffi_assert_res(call_info.media_status.try_into());
-    let call_state: pjsua_call::PjsipInvState = ffi_assert_res(call_info.state.try_into());
+        tracing::debug!(call_id, ?media_status, "on_call_media_state");
 
-    eprintln!(
-        "on_call_media_state: {:?} for call: {:?}",
-        media_status, call_id
-    );
+        let Some(state_changed_user_data) =
+            (pjsua::pjsua_call_get_user_data(call_id) as *mut StateChangedUserData).as_mut()
+        else {
+            return Ok(());
+        };
 
-    if pjsua_call::CallMediaStatus::Active == media_status
-        && pjsua_call::PjsipInvState::Confirmed == call_state
-    {
-        if let Some(call_media_data_rx) = &mut (*state_changed_user_data).call_media_data_rx {
-            match call_media_data_rx.try_recv() {
-                Ok(call_media_data) => {
-                    let call_conf_port = ffi_assert_res(pjsua_call::get_call_conf_port(call_id));
+        let media_state_info = pjsua_call::CallMediaStateInfo {
+            media_status,
+            encryption: pjsua_srtp::call_media_encryption(call_id),
+        };
 
-                    call_media_data.sinks_slots.iter().for_each(|entry| {
-                        connect_slots(call_conf_port, entry.slot);
-                    });
+        send_media_event(call_id, CallEvent::MediaStateChanged(media_state_info));
 
-                    call_media_data.stream_slots.iter().for_each(|entry| {
-                        connect_slots(entry.slot, call_conf_port);
-                    });
-                }
-                _ => {
-                    eprintln!("No call media data received!");
-                    ffi_assert!(false);
-                }
-            };
+        if pjsua_call::CallMediaStatus::Active == media_status
+            && PjsipInvState::Confirmed == call_state
+        {
+            connect_call_media(call_id, state_changed_user_data)?;
         }
-    }
+
+        Ok(())
+    });
 }
 
 pub struct PjsuaConfig {
     pjsua_config: Box<pjsua::pjsua_config>,
+    _strings_owned: Vec<CString>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PjsuaConfigError {
+    InteriorNul(&'static str),
+    TooManyEntries(&'static str, usize),
+    InvalidValue(&'static str, String),
 }
 
+impl std::fmt::Display for PjsuaConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PjsuaConfigError::InteriorNul(field) => {
+                write!(f, "field contains an interior NUL byte: {}", field)
+            }
+            PjsuaConfigError::TooManyEntries(field, max) => {
+                write!(f, "too many entries in {}, at most {} allowed", field, max)
+            }
+            PjsuaConfigError::InvalidValue(field, reason) => {
+                write!(f, "invalid value of {}: {}", field, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PjsuaConfigError {}
+
 impl PjsuaConfig {
     pub fn new() -> Self {
         unsafe {
@@ -157,9 +405,227 @@ impl PjsuaConfig {
             pjsua_config.cb.on_media_event = Some(on_media_event);
             pjsua_config.cb.on_call_media_state = Some(on_call_media_state);
             pjsua_config.cb.on_create_media_transport = Some(on_create_media_transport);
+            pjsua_config.cb.on_nat_detect = Some(pjsua_nat::on_nat_detect);
+            pjsua_config.cb.on_transport_state = Some(transport::on_transport_state);
+            pjsua_config.cb.on_call_media_transport_state = Some(on_call_media_transport_state);
+
+            PjsuaConfig {
+                pjsua_config,
+                _strings_owned: Vec::new(),
+            }
+        }
+    }
+
+    //the returned pj_str_t stays valid for as long as this PjsuaConfig lives.
+    fn own_string(
+        &mut self,
+        field: &'static str,
+        string: &str,
+    ) -> Result<pjsua::pj_str_t, PjsuaConfigError> {
+        let string = CString::new(string).map_err(|_| PjsuaConfigError::InteriorNul(field))?;
+        let pj_string = unsafe { pjsua::pj_str(string.as_ptr() as *mut i8) };
+        self._strings_owned.push(string);
+
+        Ok(pj_string)
+    }
+
+    //servers are given as "host[:port]" and tried in order.
+    pub fn set_stun_servers(&mut self, servers: &[&str]) -> Result<(), PjsuaConfigError> {
+        let max = self.pjsua_config.stun_srv.len();
 
-            PjsuaConfig { pjsua_config }
+        if servers.len() > max {
+            return Err(PjsuaConfigError::TooManyEntries("stun_srv", max));
         }
+
+        for (i, server) in servers.iter().enumerate() {
+            self.pjsua_config.stun_srv[i] = self.own_string("stun_srv", server)?;
+        }
+
+        self.pjsua_config.stun_srv_cnt = servers.len() as u32;
+
+        Ok(())
+    }
+
+    //global SRTP settings, used by accounts that do not override them.
+    pub fn set_srtp(
+        &mut self,
+        srtp_config: &pjsua_srtp::SrtpConfig,
+    ) -> Result<(), pjsua_srtp::SrtpConfigError> {
+        let srtp_config = srtp_config.to_raw()?;
+
+        self.pjsua_config.use_srtp = srtp_config.use_srtp;
+        self.pjsua_config.srtp_opt = srtp_config.srtp_opt;
+
+        if let Some(secure_signaling) = srtp_config.secure_signaling {
+            self.pjsua_config.srtp_secure_signaling = secure_signaling;
+        }
+
+        Ok(())
+    }
+
+    //when set, pjsua keeps starting up even if no STUN server could be resolved.
+    pub fn set_stun_ignore_failure(&mut self, ignore_failure: bool) {
+        self.pjsua_config.stun_ignore_failure = ignore_failure as pjsua::pj_bool_t;
+    }
+
+    //pjsua allocates call slots up to PJSUA_MAX_CALLS at compile time, the default is 4.
+    pub fn set_max_calls(&mut self, max_calls: u32) -> Result<(), PjsuaConfigError> {
+        let limit = pjsua::PJSUA_MAX_CALLS as u32;
+
+        if max_calls == 0 || max_calls > limit {
+            return Err(PjsuaConfigError::InvalidValue(
+                "max_calls",
+                format!("{} not in 1..={}", max_calls, limit),
+            ));
+        }
+
+        self.pjsua_config.max_calls = max_calls;
+
+        Ok(())
+    }
+
+    //number of pjsua worker threads polling the SIP stack, 0 requires polling with
+    //pjsua_handle_events.
+    pub fn set_thread_cnt(&mut self, thread_cnt: u32) {
+        self.pjsua_config.thread_cnt = thread_cnt;
+    }
+
+    pub fn set_user_agent(&mut self, user_agent: &str) -> Result<(), PjsuaConfigError> {
+        self.pjsua_config.user_agent = self.own_string("user_agent", user_agent)?;
+
+        Ok(())
+    }
+
+    //DNS servers used for SIP (and TURN/STUN) resolution instead of the OS resolver.
+    pub fn set_nameservers(&mut self, nameservers: &[&str]) -> Result<(), PjsuaConfigError> {
+        let max = self.pjsua_config.nameserver.len();
+
+        if nameservers.len() > max {
+            return Err(PjsuaConfigError::TooManyEntries("nameserver", max));
+        }
+
+        for (i, nameserver) in nameservers.iter().enumerate() {
+            self.pjsua_config.nameserver[i] = self.own_string("nameserver", nameserver)?;
+        }
+
+        self.pjsua_config.nameserver_count = nameservers.len() as u32;
+
+        Ok(())
+    }
+
+    //proxies are given as SIP URIs and prepended to the route set of every account.
+    pub fn set_outbound_proxies(&mut self, proxies: &[&str]) -> Result<(), PjsuaConfigError> {
+        let max = self.pjsua_config.outbound_proxy.len();
+
+        if proxies.len() > max {
+            return Err(PjsuaConfigError::TooManyEntries("outbound_proxy", max));
+        }
+
+        for (i, proxy) in proxies.iter().enumerate() {
+            self.pjsua_config.outbound_proxy[i] = self.own_string("outbound_proxy", proxy)?;
+        }
+
+        self.pjsua_config.outbound_proxy_cnt = proxies.len() as u32;
+
+        Ok(())
+    }
+
+    pub fn set_require_100rel(&mut self, use_100rel: Use100rel) {
+        self.pjsua_config.require_100rel = use_100rel.as_raw();
+    }
+
+    //setting is left at pjsip defaults when None.
+    pub fn set_session_timer(
+        &mut self,
+        timer_use: SessionTimerUse,
+        setting: Option<SessionTimerSetting>,
+    ) -> Result<(), PjsuaConfigError> {
+        if let Some(setting) = setting {
+            setting.validate()?;
+
+            self.pjsua_config.timer_setting.sess_expires = setting.session_expires.as_secs() as u32;
+            self.pjsua_config.timer_setting.min_se = setting.min_se.as_secs() as u32;
+        }
+
+        self.pjsua_config.use_timer = timer_use.as_raw();
+
+        Ok(())
+    }
+
+    //accept NOTIFY message-summary requests without a prior SUBSCRIBE.
+    pub fn set_enable_unsolicited_mwi(&mut self, enable: bool) {
+        self.pjsua_config.enable_unsolicited_mwi = enable as pjsua::pj_bool_t;
+    }
+
+    //when set, only the first forked dialog of an outgoing call is kept, others are hung up.
+    pub fn set_hangup_forked_call(&mut self, hangup: bool) {
+        self.pjsua_config.hangup_forked_call = hangup as pjsua::pj_bool_t;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Use100rel {
+    NotUsed,
+    Mandatory,
+    Optional,
+}
+
+impl Use100rel {
+    fn as_raw(&self) -> pjsua::pjsua_100rel_use {
+        match self {
+            Use100rel::NotUsed => pjsua::pjsua_100rel_use_PJSUA_100REL_NOT_USED,
+            Use100rel::Mandatory => pjsua::pjsua_100rel_use_PJSUA_100REL_MANDATORY,
+            Use100rel::Optional => pjsua::pjsua_100rel_use_PJSUA_100REL_OPTIONAL,
+        }
+    }
+}
+
+//RFC 4028 session timers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionTimerUse {
+    Inactive,
+    Optional,
+    Required,
+    Always,
+}
+
+impl SessionTimerUse {
+    fn as_raw(&self) -> pjsua::pjsua_sip_timer_use {
+        match self {
+            SessionTimerUse::Inactive => pjsua::pjsua_sip_timer_use_PJSUA_SIP_TIMER_INACTIVE,
+            SessionTimerUse::Optional => pjsua::pjsua_sip_timer_use_PJSUA_SIP_TIMER_OPTIONAL,
+            SessionTimerUse::Required => pjsua::pjsua_sip_timer_use_PJSUA_SIP_TIMER_REQUIRED,
+            SessionTimerUse::Always => pjsua::pjsua_sip_timer_use_PJSUA_SIP_TIMER_ALWAYS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionTimerSetting {
+    pub session_expires: std::time::Duration,
+    pub min_se: std::time::Duration,
+}
+
+impl SessionTimerSetting {
+    fn validate(&self) -> Result<(), PjsuaConfigError> {
+        //RFC 4028 section 4, Min-SE must not be lower than 90 seconds.
+        if self.min_se.as_secs() < 90 {
+            return Err(PjsuaConfigError::InvalidValue(
+                "timer_setting.min_se",
+                format!("{}s is below 90s", self.min_se.as_secs()),
+            ));
+        }
+
+        if self.session_expires < self.min_se {
+            return Err(PjsuaConfigError::InvalidValue(
+                "timer_setting.sess_expires",
+                "lower than min_se".to_string(),
+            ));
+        }
+
+        Ok(())
     }
 }
 
@@ -167,19 +633,49 @@ pub struct LogConfig {
     logging_cfg: Box<pjsua::pjsua_logging_config>,
 }
 
+//forwards pjsip logs into tracing at debug verbosity.
 impl Default for LogConfig {
     fn default() -> Self {
+        LogConfig::tracing(LogLevel::Debug)
+    }
+}
+
+impl LogConfig {
+    fn new_raw() -> Box<pjsua::pjsua_logging_config> {
         unsafe {
             let mut log_cfg =
                 Box::new(MaybeUninit::<pjsua::pjsua_logging_config>::zeroed().assume_init());
             pjsua::pjsua_logging_config_default(log_cfg.as_mut());
 
-            log_cfg.console_level = 4;
-            log_cfg.level = 4;
+            log_cfg
+        }
+    }
 
-            Self {
-                logging_cfg: log_cfg,
-            }
+    //pjsip log lines become tracing events with target "pjsip" and a sender field.
+    //The level can be changed later with pjsua_log::set_log_level.
+    pub fn tracing(level: LogLevel) -> Self {
+        let mut log_cfg = Self::new_raw();
+
+        log_cfg.level = level.as_raw();
+        //the writer is only called up to console_level, leave filtering to level.
+        log_cfg.console_level = LogLevel::Detailed.as_raw();
+        log_cfg.decor = pjsua::pj_log_decoration_PJ_LOG_HAS_SENDER as _;
+        log_cfg.cb = Some(pjsua_log::on_log);
+
+        Self {
+            logging_cfg: log_cfg,
+        }
+    }
+
+    //pjsip's own writer, printing to stdout.
+    pub fn console(level: LogLevel) -> Self {
+        let mut log_cfg = Self::new_raw();
+
+        log_cfg.level = level.as_raw();
+        log_cfg.console_level = level.as_raw();
+
+        Self {
+            logging_cfg: log_cfg,
         }
     }
 }
@@ -198,6 +694,20 @@ impl AsMut<pjsua::pjsua_config> for PjsuaConfig {
 
 pub struct MediaConfig {
     media_cfg: Box<pjsua::pjsua_media_config>,
+    sound_device: SoundDevice,
+    _strings_owned: Vec<CString>,
+}
+
+//what drives the conference bridge clock.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SoundDevice {
+    //pjsua_set_null_snd_dev, a null device ticks the bridge in real time from its own thread.
+    #[default]
+    Null,
+    //pjsua_set_no_snd_dev, nothing ticks the bridge. The caller drives it through
+    //PjsuaInstanceStarted::master_port, e.g. faster than real time in simulations.
+    ManualClock,
 }
 
 impl Default for MediaConfig {
@@ -212,13 +722,340 @@ impl Default for MediaConfig {
             media_cfg.ec_tail_len = 0;
             media_cfg.snd_auto_close_time = 0;
 
-            Self { media_cfg }
+            Self {
+                media_cfg,
+                sound_device: SoundDevice::Null,
+                _strings_owned: Vec::new(),
+            }
         }
     }
 }
 
+impl MediaConfig {
+    //starts from the pjsua defaults, unlike MediaConfig::default.
+    pub fn builder() -> MediaConfigBuilder {
+        MediaConfigBuilder::default()
+    }
+
+    pub fn set_sound_device(&mut self, sound_device: SoundDevice) {
+        self.sound_device = sound_device;
+    }
+
+    pub fn sound_device(&self) -> SoundDevice {
+        self.sound_device
+    }
+
+    //global ICE settings, used by accounts that do not override them.
+    pub fn set_ice(&mut self, ice_config: &pjsua_ice::IceConfig) {
+        let ice_config = ice_config.to_raw();
+
+        self.media_cfg.enable_ice = ice_config.enable_ice;
+        self.media_cfg.ice_max_host_cands = ice_config.ice_max_host_cands;
+        self.media_cfg.ice_opt = ice_config.ice_opt;
+        self.media_cfg.ice_no_rtcp = ice_config.ice_no_rtcp;
+    }
+
+    //global TURN settings, used by accounts that do not override them.
+    pub fn set_turn(
+        &mut self,
+        turn_config: &pjsua_ice::TurnConfig,
+    ) -> Result<(), PjsuaConfigError> {
+        let (turn_config, strings_owned) = turn_config.to_raw()?;
+
+        self.media_cfg.enable_turn = turn_config.enable_turn;
+        self.media_cfg.turn_server = turn_config.turn_server;
+        self.media_cfg.turn_conn_type = turn_config.turn_conn_type;
+        self.media_cfg.turn_auth_cred = turn_config.turn_auth_cred;
+
+        self._strings_owned.extend(strings_owned);
+
+        Ok(())
+    }
+}
+
 impl AsMut<pjsua::pjsua_media_config> for MediaConfig {
     fn as_mut(&mut self) -> &mut pjsua::pjsua_media_config {
         self.media_cfg.as_mut()
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EchoCancellerAlgorithm {
+    Default,
+    Speex,
+    Simple,
+    Webrtc,
+    WebrtcAec3,
+}
+
+impl EchoCancellerAlgorithm {
+    fn as_raw(&self) -> u32 {
+        match self {
+            EchoCancellerAlgorithm::Default => pjsua::pjmedia_echo_flag_PJMEDIA_ECHO_DEFAULT,
+            EchoCancellerAlgorithm::Speex => pjsua::pjmedia_echo_flag_PJMEDIA_ECHO_SPEEX,
+            EchoCancellerAlgorithm::Simple => pjsua::pjmedia_echo_flag_PJMEDIA_ECHO_SIMPLE,
+            EchoCancellerAlgorithm::Webrtc => pjsua::pjmedia_echo_flag_PJMEDIA_ECHO_WEBRTC,
+            EchoCancellerAlgorithm::WebrtcAec3 => pjsua::pjmedia_echo_flag_PJMEDIA_ECHO_WEBRTC_AEC3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub struct EchoCancellerConfig {
+    //0 disables echo cancellation.
+    pub tail_len_ms: u32,
+    pub algorithm: EchoCancellerAlgorithm,
+    pub noise_suppressor: bool,
+}
+
+//jitter buffer sizes in milliseconds, None keeps the pjmedia default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(default)]
+pub struct JitterBufferConfig {
+    pub init: Option<u32>,
+    pub min_prefetch: Option<u32>,
+    pub max_prefetch: Option<u32>,
+    pub max: Option<u32>,
+}
+
+impl JitterBufferConfig {
+    fn validate(&self) -> Result<(), PjsuaConfigError> {
+        if let (Some(min_prefetch), Some(max_prefetch)) = (self.min_prefetch, self.max_prefetch) {
+            if min_prefetch > max_prefetch {
+                return Err(PjsuaConfigError::InvalidValue(
+                    "jb_min_pre",
+                    format!("{} is above jb_max_pre {}", min_prefetch, max_prefetch),
+                ));
+            }
+        }
+
+        if let (Some(max_prefetch), Some(max)) = (self.max_prefetch, self.max) {
+            if max_prefetch > max {
+                return Err(PjsuaConfigError::InvalidValue(
+                    "jb_max_pre",
+                    format!("{} is above jb_max {}", max_prefetch, max),
+                ));
+            }
+        }
+
+        if let (Some(init), Some(max)) = (self.init, self.max) {
+            if init > max {
+                return Err(PjsuaConfigError::InvalidValue(
+                    "jb_init",
+                    format!("{} is above jb_max {}", init, max),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MediaConfigBuilder {
+    clock_rate: Option<u32>,
+    snd_clock_rate: Option<u32>,
+    channel_count: Option<u32>,
+    audio_frame_ptime: Option<u32>,
+    max_media_ports: Option<u32>,
+    quality: Option<u32>,
+    ptime: Option<u32>,
+    jitter_buffer: JitterBufferConfig,
+    echo_canceller: Option<EchoCancellerConfig>,
+    vad: Option<bool>,
+    sound_device: SoundDevice,
+}
+
+impl MediaConfigBuilder {
+    //conference bridge clock rate in Hz.
+    pub fn clock_rate(mut self, clock_rate: u32) -> Self {
+        self.clock_rate = Some(clock_rate);
+        self
+    }
+
+    //sound device clock rate in Hz, 0 uses the conference bridge clock rate.
+    pub fn snd_clock_rate(mut self, snd_clock_rate: u32) -> Self {
+        self.snd_clock_rate = Some(snd_clock_rate);
+        self
+    }
+
+    pub fn channel_count(mut self, channel_count: u32) -> Self {
+        self.channel_count = Some(channel_count);
+        self
+    }
+
+    //conference bridge frame length in milliseconds.
+    pub fn audio_frame_ptime(mut self, audio_frame_ptime: u32) -> Self {
+        self.audio_frame_ptime = Some(audio_frame_ptime);
+        self
+    }
+
+    pub fn max_media_ports(mut self, max_media_ports: u32) -> Self {
+        self.max_media_ports = Some(max_media_ports);
+        self
+    }
+
+    //media quality, 1 (fastest) to 10 (best).
+    pub fn quality(mut self, quality: u32) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    //codec ptime in milliseconds, 0 uses the codec default.
+    pub fn ptime(mut self, ptime: u32) -> Self {
+        self.ptime = Some(ptime);
+        self
+    }
+
+    pub fn jitter_buffer(mut self, jitter_buffer: JitterBufferConfig) -> Self {
+        self.jitter_buffer = jitter_buffer;
+        self
+    }
+
+    pub fn echo_canceller(mut self, echo_canceller: EchoCancellerConfig) -> Self {
+        self.echo_canceller = Some(echo_canceller);
+        self
+    }
+
+    //sets no_vad to the opposite of enabled.
+    pub fn vad(mut self, enabled: bool) -> Self {
+        self.vad = Some(enabled);
+        self
+    }
+
+    pub fn no_vad(self, no_vad: bool) -> Self {
+        self.vad(!no_vad)
+    }
+
+    pub fn sound_device(mut self, sound_device: SoundDevice) -> Self {
+        self.sound_device = sound_device;
+        self
+    }
+
+    //the conference bridge divides by its port ptime, see
+    //pjmedia_api::perform_pjmedia_format_checks_zero_division.
+    fn validate_bridge_format(
+        clock_rate: u32,
+        channel_count: u32,
+        audio_frame_ptime: u32,
+    ) -> Result<(), PjsuaConfigError> {
+        if clock_rate == 0 {
+            return Err(PjsuaConfigError::InvalidValue(
+                "clock_rate",
+                "must not be 0".to_string(),
+            ));
+        }
+
+        if channel_count == 0 {
+            return Err(PjsuaConfigError::InvalidValue(
+                "channel_count",
+                "must not be 0".to_string(),
+            ));
+        }
+
+        let samples_per_frame =
+            clock_rate as u64 * channel_count as u64 * audio_frame_ptime as u64 / 1000;
+
+        let mut audio_format_detail =
+            unsafe { MaybeUninit::<pjsua::pjmedia_audio_format_detail>::zeroed().assume_init() };
+        audio_format_detail.clock_rate = clock_rate;
+        audio_format_detail.channel_count = channel_count;
+
+        pjmedia_api::perform_pjmedia_format_checks_zero_division(
+            samples_per_frame as usize,
+            &audio_format_detail,
+        )
+        .map_err(|e| PjsuaConfigError::InvalidValue("audio_frame_ptime", e.to_string()))
+    }
+
+    pub fn build(self) -> Result<MediaConfig, PjsuaConfigError> {
+        let mut media_cfg =
+            Box::new(unsafe { MaybeUninit::<pjsua::pjsua_media_config>::zeroed().assume_init() });
+
+        unsafe {
+            pjsua::pjsua_media_config_default(media_cfg.as_mut());
+        }
+
+        let clock_rate = self.clock_rate.unwrap_or(media_cfg.clock_rate);
+        let channel_count = self.channel_count.unwrap_or(media_cfg.channel_count);
+        let audio_frame_ptime = self
+            .audio_frame_ptime
+            .unwrap_or(media_cfg.audio_frame_ptime);
+
+        Self::validate_bridge_format(clock_rate, channel_count, audio_frame_ptime)?;
+
+        media_cfg.clock_rate = clock_rate;
+        media_cfg.channel_count = channel_count;
+        media_cfg.audio_frame_ptime = audio_frame_ptime;
+
+        if let Some(snd_clock_rate) = self.snd_clock_rate {
+            media_cfg.snd_clock_rate = snd_clock_rate;
+        }
+
+        if let Some(max_media_ports) = self.max_media_ports {
+            if max_media_ports == 0 {
+                return Err(PjsuaConfigError::InvalidValue(
+                    "max_media_ports",
+                    "must not be 0".to_string(),
+                ));
+            }
+
+            media_cfg.max_media_ports = max_media_ports;
+        }
+
+        if let Some(quality) = self.quality {
+            if !(1..=10).contains(&quality) {
+                return Err(PjsuaConfigError::InvalidValue(
+                    "quality",
+                    format!("{} not in 1..=10", quality),
+                ));
+            }
+
+            media_cfg.quality = quality;
+        }
+
+        if let Some(ptime) = self.ptime {
+            media_cfg.ptime = ptime;
+        }
+
+        self.jitter_buffer.validate()?;
+
+        if let Some(init) = self.jitter_buffer.init {
+            media_cfg.jb_init = init as i32;
+        }
+
+        if let Some(min_prefetch) = self.jitter_buffer.min_prefetch {
+            media_cfg.jb_min_pre = min_prefetch as i32;
+        }
+
+        if let Some(max_prefetch) = self.jitter_buffer.max_prefetch {
+            media_cfg.jb_max_pre = max_prefetch as i32;
+        }
+
+        if let Some(max) = self.jitter_buffer.max {
+            media_cfg.jb_max = max as i32;
+        }
+
+        if let Some(echo_canceller) = self.echo_canceller {
+            let mut ec_options = echo_canceller.algorithm.as_raw();
+
+            if echo_canceller.noise_suppressor {
+                ec_options |= pjsua::pjmedia_echo_flag_PJMEDIA_ECHO_USE_NOISE_SUPPRESSOR;
+            }
+
+            media_cfg.ec_tail_len = echo_canceller.tail_len_ms;
+            media_cfg.ec_options = ec_options;
+        }
+
+        if let Some(vad) = self.vad {
+            media_cfg.no_vad = !vad as pjsua::pj_bool_t;
+        }
+
+        Ok(MediaConfig {
+            media_cfg,
+            sound_device: self.sound_device,
+            _strings_owned: Vec::new(),
+        })
+    }
+}
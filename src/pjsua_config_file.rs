@@ -0,0 +1,488 @@
+use crate::pjsua_account_config::{
+    AccountConfig, AccountConfigBuilder, SipUriScheme, UriTransportParam,
+};
+use crate::pjsua_config::{
+    EchoCancellerConfig, JitterBufferConfig, LogConfig, MediaConfig, PjsuaConfig, PjsuaConfigError,
+    SessionTimerSetting, SessionTimerUse, SoundDevice, Use100rel,
+};
+use crate::pjsua_log::LogLevel;
+use crate::pjsua_softphone_api::{Softphone, SoftphoneBuilder, SoftphoneError};
+use crate::transport::{PjsuaTransport, TlsConfig, TlsConfigError, TlsMethod, TransportType};
+
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+//secrets can be given inline, or as references resolved when the config is turned into a
+//builder, e.g. password = { env = "SIP_PASSWORD" } or password = { file = "/run/secrets/sip" }.
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Secret {
+    Value(String),
+    Env { env: String },
+    File { file: PathBuf },
+}
+
+//inline values are never printed, so configs can be logged with {:?}.
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Secret::Value(_) => write!(f, "Value(<redacted>)"),
+            Secret::Env { env } => f.debug_struct("Env").field("env", env).finish(),
+            Secret::File { file } => f.debug_struct("File").field("file", file).finish(),
+        }
+    }
+}
+
+impl Secret {
+    fn resolve(&self, field: &'static str) -> Result<String, ConfigFileError> {
+        match self {
+            Secret::Value(value) => Ok(value.clone()),
+            Secret::Env { env } => std::env::var(env).map_err(|e| ConfigFileError::Secret {
+                field,
+                reason: format!("environment variable {}: {}", env, e),
+            }),
+            //trailing newlines are stripped, as most secret files end with one.
+            Secret::File { file } => std::fs::read_to_string(file)
+                .map(|value| value.trim_end_matches(['\r', '\n']).to_string())
+                .map_err(|e| ConfigFileError::Secret {
+                    field,
+                    reason: format!("file {:?}: {}", file, e),
+                }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UaFileConfig {
+    pub max_calls: Option<u32>,
+    pub thread_cnt: Option<u32>,
+    pub user_agent: Option<String>,
+    pub nameservers: Vec<String>,
+    pub stun_servers: Vec<String>,
+    pub stun_ignore_failure: Option<bool>,
+    pub outbound_proxies: Vec<String>,
+    pub require_100rel: Option<Use100rel>,
+    pub session_timer: Option<SessionTimerFileConfig>,
+    pub enable_unsolicited_mwi: Option<bool>,
+    pub hangup_forked_call: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionTimerFileConfig {
+    #[serde(rename = "use")]
+    pub timer_use: SessionTimerUse,
+    pub session_expires_secs: Option<u64>,
+    pub min_se_secs: Option<u64>,
+}
+
+impl UaFileConfig {
+    fn to_pjsua_config(&self) -> Result<PjsuaConfig, PjsuaConfigError> {
+        let mut pjsua_config = PjsuaConfig::new();
+
+        if let Some(max_calls) = self.max_calls {
+            pjsua_config.set_max_calls(max_calls)?;
+        }
+
+        if let Some(thread_cnt) = self.thread_cnt {
+            pjsua_config.set_thread_cnt(thread_cnt);
+        }
+
+        if let Some(user_agent) = &self.user_agent {
+            pjsua_config.set_user_agent(user_agent)?;
+        }
+
+        if !self.nameservers.is_empty() {
+            pjsua_config.set_nameservers(&as_strs(&self.nameservers))?;
+        }
+
+        if !self.stun_servers.is_empty() {
+            pjsua_config.set_stun_servers(&as_strs(&self.stun_servers))?;
+        }
+
+        if let Some(stun_ignore_failure) = self.stun_ignore_failure {
+            pjsua_config.set_stun_ignore_failure(stun_ignore_failure);
+        }
+
+        if !self.outbound_proxies.is_empty() {
+            pjsua_config.set_outbound_proxies(&as_strs(&self.outbound_proxies))?;
+        }
+
+        if let Some(require_100rel) = self.require_100rel {
+            pjsua_config.set_require_100rel(require_100rel);
+        }
+
+        if let Some(session_timer) = &self.session_timer {
+            let setting = match (
+                session_timer.session_expires_secs,
+                session_timer.min_se_secs,
+            ) {
+                (Some(session_expires), Some(min_se)) => Some(SessionTimerSetting {
+                    session_expires: Duration::from_secs(session_expires),
+                    min_se: Duration::from_secs(min_se),
+                }),
+                (None, None) => None,
+                _ => {
+                    return Err(PjsuaConfigError::InvalidValue(
+                        "session_timer",
+                        "session_expires_secs and min_se_secs must be given together".to_string(),
+                    ))
+                }
+            };
+
+            pjsua_config.set_session_timer(session_timer.timer_use, setting)?;
+        }
+
+        if let Some(enable_unsolicited_mwi) = self.enable_unsolicited_mwi {
+            pjsua_config.set_enable_unsolicited_mwi(enable_unsolicited_mwi);
+        }
+
+        if let Some(hangup_forked_call) = self.hangup_forked_call {
+            pjsua_config.set_hangup_forked_call(hangup_forked_call);
+        }
+
+        Ok(pjsua_config)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogFileConfig {
+    pub level: LogLevel,
+    //print with pjsip's own writer instead of forwarding to tracing.
+    pub console: bool,
+}
+
+impl Default for LogFileConfig {
+    fn default() -> Self {
+        LogFileConfig {
+            level: LogLevel::Debug,
+            console: false,
+        }
+    }
+}
+
+impl LogFileConfig {
+    fn to_log_config(&self) -> LogConfig {
+        match self.console {
+            true => LogConfig::console(self.level),
+            false => LogConfig::tracing(self.level),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MediaFileConfig {
+    pub clock_rate: Option<u32>,
+    pub snd_clock_rate: Option<u32>,
+    pub channel_count: Option<u32>,
+    pub audio_frame_ptime: Option<u32>,
+    pub max_media_ports: Option<u32>,
+    pub quality: Option<u32>,
+    pub ptime: Option<u32>,
+    pub jitter_buffer: JitterBufferConfig,
+    pub echo_canceller: Option<EchoCancellerConfig>,
+    pub vad: Option<bool>,
+    pub sound_device: SoundDevice,
+}
+
+impl MediaFileConfig {
+    fn to_media_config(&self) -> Result<MediaConfig, PjsuaConfigError> {
+        let mut builder = MediaConfig::builder().jitter_buffer(self.jitter_buffer);
+
+        if let Some(clock_rate) = self.clock_rate {
+            builder = builder.clock_rate(clock_rate);
+        }
+
+        if let Some(snd_clock_rate) = self.snd_clock_rate {
+            builder = builder.snd_clock_rate(snd_clock_rate);
+        }
+
+        if let Some(channel_count) = self.channel_count {
+            builder = builder.channel_count(channel_count);
+        }
+
+        if let Some(audio_frame_ptime) = self.audio_frame_ptime {
+            builder = builder.audio_frame_ptime(audio_frame_ptime);
+        }
+
+        if let Some(max_media_ports) = self.max_media_ports {
+            builder = builder.max_media_ports(max_media_ports);
+        }
+
+        if let Some(quality) = self.quality {
+            builder = builder.quality(quality);
+        }
+
+        if let Some(ptime) = self.ptime {
+            builder = builder.ptime(ptime);
+        }
+
+        if let Some(echo_canceller) = self.echo_canceller {
+            builder = builder.echo_canceller(echo_canceller);
+        }
+
+        if let Some(vad) = self.vad {
+            builder = builder.vad(vad);
+        }
+
+        builder.sound_device(self.sound_device).build()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransportFileConfig {
+    #[serde(rename = "type")]
+    pub transport_type: TransportType,
+    pub port: Option<u16>,
+    pub bound_addr: Option<IpAddr>,
+    pub public_addr: Option<String>,
+    //required for tls and tls6 transports.
+    pub tls: Option<TlsFileConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsFileConfig {
+    pub ca_list_file: Option<PathBuf>,
+    pub cert_file: Option<PathBuf>,
+    pub privkey_file: Option<PathBuf>,
+    pub privkey_password: Option<Secret>,
+    pub method: TlsMethod,
+    pub ciphers: Vec<String>,
+    pub verify_server: bool,
+    pub verify_client: bool,
+    pub require_client_cert: bool,
+}
+
+impl TransportFileConfig {
+    fn to_transport(&self, index: usize) -> Result<PjsuaTransport, ConfigFileError> {
+        let tls_config = || -> Result<TlsConfig, ConfigFileError> {
+            let tls = self
+                .tls
+                .as_ref()
+                .ok_or(ConfigFileError::MissingTls(index))?;
+
+            let privkey_password = tls
+                .privkey_password
+                .as_ref()
+                .map(|password| password.resolve("privkey_password"))
+                .transpose()?;
+
+            Ok(TlsConfig {
+                ca_list_file: tls.ca_list_file.clone(),
+                cert_file: tls.cert_file.clone(),
+                privkey_file: tls.privkey_file.clone(),
+                privkey_password,
+                method: tls.method,
+                ciphers: tls.ciphers.clone(),
+                verify_server: tls.verify_server,
+                verify_client: tls.verify_client,
+                require_client_cert: tls.require_client_cert,
+            })
+        };
+
+        let tls_error = |e| ConfigFileError::InvalidTls(index, e);
+
+        let mut transport = match self.transport_type {
+            TransportType::Udp => PjsuaTransport::new(self.port),
+            TransportType::Tcp => PjsuaTransport::tcp(self.port),
+            TransportType::Tls => {
+                PjsuaTransport::tls(self.port, tls_config()?).map_err(tls_error)?
+            }
+            TransportType::Udp6 => PjsuaTransport::udp6(self.port),
+            TransportType::Tcp6 => PjsuaTransport::tcp6(self.port),
+            TransportType::Tls6 => {
+                PjsuaTransport::tls6(self.port, tls_config()?).map_err(tls_error)?
+            }
+        };
+
+        if let Some(bound_addr) = self.bound_addr {
+            transport.set_bound_addr(bound_addr);
+        }
+
+        if let Some(public_addr) = &self.public_addr {
+            transport.set_public_addr(public_addr).map_err(|_| {
+                ConfigFileError::InvalidConfig(PjsuaConfigError::InteriorNul("public_addr"))
+            })?;
+        }
+
+        Ok(transport)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountFileConfig {
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub scheme: SipUriScheme,
+    pub user: String,
+    pub password: Option<Secret>,
+    pub host: String,
+    pub port: Option<u16>,
+    pub transport: Option<UriTransportParam>,
+    pub registrar_uri: Option<String>,
+}
+
+impl AccountFileConfig {
+    fn to_builder(&self) -> Result<AccountConfigBuilder, ConfigFileError> {
+        let mut builder = AccountConfig::builder()
+            .scheme(self.scheme)
+            .user(self.user.as_str())
+            .host(self.host.as_str());
+
+        if let Some(display_name) = &self.display_name {
+            builder = builder.display_name(display_name.as_str());
+        }
+
+        if let Some(password) = &self.password {
+            builder = builder.password(password.resolve("password")?);
+        }
+
+        if let Some(port) = self.port {
+            builder = builder.port(port);
+        }
+
+        if let Some(transport) = self.transport {
+            builder = builder.transport(transport);
+        }
+
+        if let Some(registrar_uri) = &self.registrar_uri {
+            builder = builder.registrar_uri(registrar_uri.as_str());
+        }
+
+        Ok(builder)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CodecPriorityFileConfig {
+    pub codec: String,
+    pub priority: u8,
+}
+
+//top level of a softphone config file. Only transports are required.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SoftphoneFileConfig {
+    #[serde(default)]
+    pub ua: UaFileConfig,
+    #[serde(default)]
+    pub log: LogFileConfig,
+    #[serde(default)]
+    pub media: MediaFileConfig,
+    pub transports: Vec<TransportFileConfig>,
+    #[serde(default)]
+    pub accounts: Vec<AccountFileConfig>,
+    #[serde(default)]
+    pub codecs: Vec<CodecPriorityFileConfig>,
+}
+
+#[derive(Debug)]
+pub enum ConfigFileError {
+    Io(PathBuf, std::io::Error),
+    UnknownFormat(PathBuf),
+    Parse(String),
+    Secret { field: &'static str, reason: String },
+    InvalidConfig(PjsuaConfigError),
+    MissingTls(usize),
+    InvalidTls(usize, TlsConfigError),
+    Setup(SoftphoneError),
+}
+
+impl std::fmt::Display for ConfigFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConfigFileError::Io(path, e) => write!(f, "reading {:?} failed: {}", path, e),
+            ConfigFileError::UnknownFormat(path) => {
+                write!(
+                    f,
+                    "unknown config format, expected .toml or .json: {:?}",
+                    path
+                )
+            }
+            ConfigFileError::Parse(e) => write!(f, "parsing config failed: {}", e),
+            ConfigFileError::Secret { field, reason } => {
+                write!(f, "resolving {} failed: {}", field, reason)
+            }
+            ConfigFileError::InvalidConfig(e) => write!(f, "invalid config: {}", e),
+            ConfigFileError::MissingTls(index) => {
+                write!(f, "transport #{} is TLS but has no tls section", index)
+            }
+            ConfigFileError::InvalidTls(index, e) => {
+                write!(f, "invalid tls config of transport #{}: {}", index, e)
+            }
+            ConfigFileError::Setup(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigFileError {}
+
+impl From<PjsuaConfigError> for ConfigFileError {
+    fn from(e: PjsuaConfigError) -> Self {
+        ConfigFileError::InvalidConfig(e)
+    }
+}
+
+impl SoftphoneFileConfig {
+    pub fn from_toml_str(config: &str) -> Result<Self, ConfigFileError> {
+        toml::from_str(config).map_err(|e| ConfigFileError::Parse(e.to_string()))
+    }
+
+    pub fn from_json_str(config: &str) -> Result<Self, ConfigFileError> {
+        serde_json::from_str(config).map_err(|e| ConfigFileError::Parse(e.to_string()))
+    }
+
+    //the format is picked by the file extension.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        let path = path.as_ref();
+
+        let config = std::fs::read_to_string(path)
+            .map_err(|e| ConfigFileError::Io(path.to_path_buf(), e))?;
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml_str(&config),
+            Some("json") => Self::from_json_str(&config),
+            _ => Err(ConfigFileError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+
+    //resolves secrets and converts every section, nothing is passed to pjsua yet.
+    pub fn into_builder(self) -> Result<SoftphoneBuilder, ConfigFileError> {
+        let mut builder = Softphone::builder()
+            .pjsua_config(self.ua.to_pjsua_config()?)
+            .log_config(self.log.to_log_config())
+            .media_config(self.media.to_media_config()?);
+
+        for (index, transport) in self.transports.iter().enumerate() {
+            builder = builder.transport(transport.to_transport(index)?);
+        }
+
+        for account in &self.accounts {
+            builder = builder.account(account.to_builder()?);
+        }
+
+        for codec in self.codecs {
+            builder = builder.codec_priority(codec.codec, codec.priority);
+        }
+
+        Ok(builder)
+    }
+
+    //accounts are registered with Softphone::add_accounts.
+    pub fn build(self) -> Result<Softphone, ConfigFileError> {
+        self.into_builder()?.build().map_err(ConfigFileError::Setup)
+    }
+}
+
+fn as_strs(strings: &[String]) -> Vec<&str> {
+    strings.iter().map(String::as_str).collect()
+}
@@ -0,0 +1,185 @@
+use crate::pjsua_config::PjsuaConfigError;
+
+use std::ffi::CString;
+use std::mem::MaybeUninit;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IceTrickle {
+    Disabled,
+    Half,
+    Full,
+}
+
+impl IceTrickle {
+    fn as_raw(&self) -> pjsua::pj_ice_sess_trickle {
+        match self {
+            IceTrickle::Disabled => pjsua::pj_ice_sess_trickle_PJ_ICE_SESS_TRICKLE_DISABLED,
+            IceTrickle::Half => pjsua::pj_ice_sess_trickle_PJ_ICE_SESS_TRICKLE_HALF,
+            IceTrickle::Full => pjsua::pj_ice_sess_trickle_PJ_ICE_SESS_TRICKLE_FULL,
+        }
+    }
+}
+
+//every option left as None keeps the pjsua default.
+#[derive(Debug, Clone, Default)]
+pub struct IceConfig {
+    pub enable_ice: bool,
+    pub max_host_candidates: Option<u32>,
+    pub aggressive_nomination: Option<bool>,
+    pub trickle: Option<IceTrickle>,
+    pub no_rtcp: Option<bool>,
+}
+
+impl IceConfig {
+    pub(crate) fn to_raw(&self) -> pjsua::pjsua_ice_config {
+        let mut ice_config =
+            unsafe { MaybeUninit::<pjsua::pjsua_ice_config>::zeroed().assume_init() };
+
+        unsafe {
+            pjsua::pjsua_ice_config_default(&mut ice_config);
+        }
+
+        ice_config.enable_ice = self.enable_ice as pjsua::pj_bool_t;
+
+        if let Some(max_host_candidates) = self.max_host_candidates {
+            ice_config.ice_max_host_cands = max_host_candidates as i32;
+        }
+
+        if let Some(aggressive_nomination) = self.aggressive_nomination {
+            ice_config.ice_opt.aggressive = aggressive_nomination as pjsua::pj_bool_t;
+        }
+
+        if let Some(trickle) = self.trickle {
+            ice_config.ice_opt.trickle = trickle.as_raw();
+        }
+
+        if let Some(no_rtcp) = self.no_rtcp {
+            ice_config.ice_no_rtcp = no_rtcp as pjsua::pj_bool_t;
+        }
+
+        ice_config
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurnTransport {
+    Udp,
+    Tcp,
+    Tls,
+}
+
+impl TurnTransport {
+    fn as_raw(&self) -> pjsua::pj_turn_tp_type {
+        match self {
+            TurnTransport::Udp => pjsua::pj_turn_tp_type_PJ_TURN_TP_UDP,
+            TurnTransport::Tcp => pjsua::pj_turn_tp_type_PJ_TURN_TP_TCP,
+            TurnTransport::Tls => pjsua::pj_turn_tp_type_PJ_TURN_TP_TLS,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TurnCredentials {
+    pub realm: String,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TurnConfig {
+    //"host[:port]"
+    pub server: String,
+    pub transport: TurnTransport,
+    pub credentials: Option<TurnCredentials>,
+}
+
+fn owned_pj_str(
+    field: &'static str,
+    string: &str,
+    strings_owned: &mut Vec<CString>,
+) -> Result<pjsua::pj_str_t, PjsuaConfigError> {
+    let string = CString::new(string).map_err(|_| PjsuaConfigError::InteriorNul(field))?;
+    let pj_string = unsafe { pjsua::pj_str(string.as_ptr() as *mut i8) };
+    strings_owned.push(string);
+
+    Ok(pj_string)
+}
+
+impl TurnConfig {
+    //the returned config points into the returned CStrings, which must outlive it.
+    pub(crate) fn to_raw(
+        &self,
+    ) -> Result<(pjsua::pjsua_turn_config, Vec<CString>), PjsuaConfigError> {
+        let mut turn_config =
+            unsafe { MaybeUninit::<pjsua::pjsua_turn_config>::zeroed().assume_init() };
+
+        unsafe {
+            pjsua::pjsua_turn_config_default(&mut turn_config);
+        }
+
+        let mut strings_owned = Vec::new();
+
+        turn_config.enable_turn = pjsua::pj_constants__PJ_TRUE as pjsua::pj_bool_t;
+        turn_config.turn_server = owned_pj_str("turn_server", &self.server, &mut strings_owned)?;
+        turn_config.turn_conn_type = self.transport.as_raw();
+
+        if let Some(credentials) = &self.credentials {
+            let auth_cred = &mut turn_config.turn_auth_cred;
+
+            auth_cred.type_ = pjsua::pj_stun_auth_cred_type_PJ_STUN_AUTH_CRED_STATIC;
+
+            let static_cred = unsafe { &mut auth_cred.data.static_cred };
+
+            static_cred.realm = owned_pj_str("turn_realm", &credentials.realm, &mut strings_owned)?;
+            static_cred.username =
+                owned_pj_str("turn_username", &credentials.username, &mut strings_owned)?;
+            static_cred.data_type = pjsua::pj_stun_passwd_type_PJ_STUN_PASSWD_PLAIN;
+            static_cred.data =
+                owned_pj_str("turn_password", &credentials.password, &mut strings_owned)?;
+        }
+
+        Ok((turn_config, strings_owned))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IceState {
+    Null,
+    Init,
+    Ready,
+    SessionReady,
+    Negotiating,
+    Running,
+    Failed,
+}
+
+impl TryFrom<u32> for IceState {
+    type Error = ();
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            pjsua::pj_ice_strans_state_PJ_ICE_STRANS_STATE_NULL => Ok(IceState::Null),
+            pjsua::pj_ice_strans_state_PJ_ICE_STRANS_STATE_INIT => Ok(IceState::Init),
+            pjsua::pj_ice_strans_state_PJ_ICE_STRANS_STATE_READY => Ok(IceState::Ready),
+            pjsua::pj_ice_strans_state_PJ_ICE_STRANS_STATE_SESS_READY => Ok(IceState::SessionReady),
+            pjsua::pj_ice_strans_state_PJ_ICE_STRANS_STATE_NEGO => Ok(IceState::Negotiating),
+            pjsua::pj_ice_strans_state_PJ_ICE_STRANS_STATE_RUNNING => Ok(IceState::Running),
+            pjsua::pj_ice_strans_state_PJ_ICE_STRANS_STATE_FAILED => Ok(IceState::Failed),
+            _ => Err(()),
+        }
+    }
+}
+
+//returns None if the media transport of the call is not an ICE transport.
+pub(crate) fn call_ice_state(call_id: pjsua::pjsua_call_id, media_index: u32) -> Option<IceState> {
+    unsafe {
+        crate::pjsua_call::with_transport_specific_info(
+            call_id,
+            media_index,
+            pjsua::pjmedia_transport_type_PJMEDIA_TRANSPORT_TYPE_ICE,
+            |ice_info: &pjsua::pjmedia_ice_transport_info| {
+                IceState::try_from(ice_info.sess_state as u32).ok()
+            },
+        )
+        .flatten()
+    }
+}
@@ -0,0 +1,82 @@
+use crate::error::catch_callback_panic;
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+
+//pjsip verbosity, higher levels include the lower ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Fatal = 0,
+    Error = 1,
+    Warning = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+    Detailed = 6,
+}
+
+impl LogLevel {
+    pub(crate) fn as_raw(&self) -> u32 {
+        *self as u32
+    }
+}
+
+impl TryFrom<i32> for LogLevel {
+    type Error = ();
+
+    fn try_from(level: i32) -> Result<Self, Self::Error> {
+        match level {
+            0 => Ok(LogLevel::Fatal),
+            1 => Ok(LogLevel::Error),
+            2 => Ok(LogLevel::Warning),
+            3 => Ok(LogLevel::Info),
+            4 => Ok(LogLevel::Debug),
+            5 => Ok(LogLevel::Trace),
+            6 => Ok(LogLevel::Detailed),
+            _ => Err(()),
+        }
+    }
+}
+
+//pjlib filters by this level before anything reaches the log writer.
+//Safe to call at any time, also from threads not registered with pjlib.
+pub fn set_log_level(level: LogLevel) {
+    unsafe {
+        pjsua::pj_log_set_level(level.as_raw() as c_int);
+    }
+}
+
+pub fn log_level() -> Option<LogLevel> {
+    unsafe { LogLevel::try_from(pjsua::pj_log_get_level()).ok() }
+}
+
+//installed as pjsua_logging_config.cb, the log decoration is reduced to the sender only,
+//so that every line is "<sender> <message>".
+pub(crate) unsafe extern "C" fn on_log(level: c_int, data: *const c_char, _len: c_int) {
+    let _ = catch_callback_panic("on_log", || {
+        if data.is_null() {
+            return;
+        }
+
+        let line = CStr::from_ptr(data).to_string_lossy();
+        let line = line.trim_end();
+
+        let (sender, message) = match line.split_once(char::is_whitespace) {
+            Some((sender, message)) => (sender, message.trim_start()),
+            None => ("", line),
+        };
+
+        match LogLevel::try_from(level).unwrap_or(LogLevel::Detailed) {
+            LogLevel::Fatal | LogLevel::Error => {
+                tracing::error!(target: "pjsip", sender, "{}", message)
+            }
+            LogLevel::Warning => tracing::warn!(target: "pjsip", sender, "{}", message),
+            LogLevel::Info => tracing::info!(target: "pjsip", sender, "{}", message),
+            LogLevel::Debug => tracing::debug!(target: "pjsip", sender, "{}", message),
+            LogLevel::Trace | LogLevel::Detailed => {
+                tracing::trace!(target: "pjsip", sender, "{}", message)
+            }
+        }
+    });
+}
@@ -0,0 +1,107 @@
+use crate::error::{get_error_as_result, PjsuaError};
+use crate::pjsua_softphone_api::PjsuaInstanceStarted;
+use crate::tokio_utils::register_current_thread;
+
+use std::mem::MaybeUninit;
+
+//conference bridge format, taken from the MediaConfig pjsua was initialized with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MasterPortFormat {
+    pub clock_rate: u32,
+    pub channel_count: u32,
+    pub samples_per_frame: usize,
+}
+
+//conference bridge master port returned by pjsua_set_no_snd_dev.
+//Every get_frame advances the bridge by one frame: all ports connected to the bridge are
+//read from and written to. put_frame feeds the slot 0 input, like a microphone would.
+pub struct ConferenceMasterPort<'a> {
+    port: *mut pjsua::pjmedia_port,
+    format: MasterPortFormat,
+    _pjsua_instance_started: &'a PjsuaInstanceStarted,
+}
+
+impl<'a> ConferenceMasterPort<'a> {
+    pub(crate) fn new(
+        port: *mut pjsua::pjmedia_port,
+        format: MasterPortFormat,
+        pjsua_instance_started: &'a PjsuaInstanceStarted,
+    ) -> Self {
+        ConferenceMasterPort {
+            port,
+            format,
+            _pjsua_instance_started: pjsua_instance_started,
+        }
+    }
+
+    pub fn format(&self) -> MasterPortFormat {
+        self.format
+    }
+
+    fn check_len(&self, operation: &'static str, len: usize) -> Result<(), PjsuaError> {
+        if len != self.format.samples_per_frame {
+            return Err(PjsuaError::invalid_config(
+                operation,
+                format!(
+                    "expected {} samples per frame, got {}",
+                    self.format.samples_per_frame, len
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    //runs one bridge tick and writes the slot 0 output into samples.
+    //Returns false if the bridge produced no audio, samples are zeroed then.
+    pub fn get_frame(&self, samples: &mut [i16]) -> Result<bool, PjsuaError> {
+        self.check_len("get_frame", samples.len())?;
+
+        register_current_thread()?;
+
+        let mut frame = unsafe { MaybeUninit::<pjsua::pjmedia_frame>::zeroed().assume_init() };
+        frame.type_ = pjsua::pjmedia_frame_type_PJMEDIA_FRAME_TYPE_AUDIO;
+        frame.buf = samples.as_mut_ptr() as *mut std::ffi::c_void;
+        frame.size = std::mem::size_of_val(samples);
+
+        unsafe {
+            get_error_as_result(
+                "pjmedia_port_get_frame",
+                pjsua::pjmedia_port_get_frame(self.port, &mut frame),
+            )?;
+        }
+
+        if frame.type_ != pjsua::pjmedia_frame_type_PJMEDIA_FRAME_TYPE_AUDIO {
+            samples.fill(0);
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    pub fn put_frame(&self, samples: &[i16]) -> Result<(), PjsuaError> {
+        self.check_len("put_frame", samples.len())?;
+
+        register_current_thread()?;
+
+        let mut frame = unsafe { MaybeUninit::<pjsua::pjmedia_frame>::zeroed().assume_init() };
+        frame.type_ = pjsua::pjmedia_frame_type_PJMEDIA_FRAME_TYPE_AUDIO;
+        //put_frame only reads from buf.
+        frame.buf = samples.as_ptr() as *mut std::ffi::c_void;
+        frame.size = std::mem::size_of_val(samples);
+
+        unsafe {
+            get_error_as_result(
+                "pjmedia_port_put_frame",
+                pjsua::pjmedia_port_put_frame(self.port, &mut frame),
+            )
+        }
+    }
+
+    //advances the bridge by one frame, discarding the slot 0 output.
+    pub fn tick(&self) -> Result<(), PjsuaError> {
+        let mut samples = vec![0; self.format.samples_per_frame];
+
+        self.get_frame(&mut samples).map(|_| ())
+    }
+}
@@ -1,77 +1,190 @@
-use std::ffi::CStr;
-use std::ptr::write;
+use crate::error::PjsuaError;
+use crate::tokio_utils::run_on_pjsua_thread_blocking;
+
+use std::ffi::CString;
+use std::mem::{align_of, size_of};
+use std::ptr::NonNull;
+
+//pjlib grows a pool by increment_size whenever it runs out of space, the sizes below only decide
+//how often that happens. They fit the conference bridge ports of a single call.
+pub const DEFAULT_INIT_SIZE: usize = 4000;
+pub const DEFAULT_INCREMENT_SIZE: usize = 4000;
 
 #[derive(Debug)]
 pub struct PjsuaMemoryPool {
     pjsua_pool: *mut pjsua::pj_pool_t,
 }
 
-pub struct PoolBuffer<'a, T: Sized + Default> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryPoolStats {
+    //bytes handed out by the pool, including alignment padding.
+    pub used_size: usize,
+    //bytes of all blocks the pool got from the pool factory so far.
+    pub capacity: usize,
+}
+
+//typed slice allocated from a PjsuaMemoryPool. The elements are dropped with the PoolBuffer,
+//the memory itself is only returned to pjlib when the pool is released.
+pub struct PoolBuffer<'a, T> {
     pool_buffer: &'a mut [T],
-    objects_count: usize,
     _mem_pool: &'a PjsuaMemoryPool,
 }
 
-impl<'a, T: Sized + Default> PoolBuffer<'a, T> {
-    pub fn len(&self) -> usize {
-        self.objects_count
+impl<'a, T> std::ops::Deref for PoolBuffer<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.pool_buffer
     }
 }
 
-impl<'a, T: Sized + Default> std::ops::Index<usize> for PoolBuffer<'a, T> {
-    type Output = T;
+impl<'a, T> std::ops::DerefMut for PoolBuffer<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.pool_buffer
+    }
+}
 
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.pool_buffer[index]
+impl<'a, T> AsRef<[T]> for PoolBuffer<'a, T> {
+    fn as_ref(&self) -> &[T] {
+        self.pool_buffer
     }
 }
 
-impl<'a, T: Sized + Default> AsMut<T> for PoolBuffer<'a, T> {
-    fn as_mut(&mut self) -> &mut T {
-        &mut self.pool_buffer[0]
+impl<'a, T> AsMut<[T]> for PoolBuffer<'a, T> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self.pool_buffer
+    }
+}
+
+impl<'a, T: std::fmt::Debug> std::fmt::Debug for PoolBuffer<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.pool_buffer.iter()).finish()
+    }
+}
+
+impl<'a, T> Drop for PoolBuffer<'a, T> {
+    fn drop(&mut self) {
+        unsafe { std::ptr::drop_in_place(&mut *self.pool_buffer as *mut [T]) };
     }
 }
 
 impl PjsuaMemoryPool {
     pub fn new(init_size: usize, increment_size: usize) -> Option<PjsuaMemoryPool> {
-        let name = CStr::from_bytes_with_nul(b"pjsua_buffer_rust\0").unwrap();
+        Self::with_name("pjsua_buffer_rust", init_size, increment_size)
+    }
 
-        let pool = unsafe { pjsua::pjsua_pool_create(name.as_ptr(), init_size, increment_size) };
+    //the name shows up in pjsua pool dumps, pjlib truncates it to PJ_MAX_OBJ_NAME.
+    //The pool is created and released on the pjsua worker.
+    pub fn with_name(
+        name: &str,
+        init_size: usize,
+        increment_size: usize,
+    ) -> Option<PjsuaMemoryPool> {
+        let name = CString::new(name).ok()?;
+
+        let pool = unsafe {
+            run_on_pjsua_thread_blocking(|| {
+                pjsua::pjsua_pool_create(name.as_ptr(), init_size, increment_size)
+            })
+        };
 
         match pool {
-            pool if pool.is_null() => None,
-            pool => Some(PjsuaMemoryPool { pjsua_pool: pool }),
+            Ok(pool) if pool.is_null() => None,
+            Ok(pool) => Some(PjsuaMemoryPool { pjsua_pool: pool }),
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to create memory pool");
+                None
+            }
         }
     }
 
+    pub fn with_default_size() -> Option<PjsuaMemoryPool> {
+        Self::new(DEFAULT_INIT_SIZE, DEFAULT_INCREMENT_SIZE)
+    }
+
     pub fn raw_handle(&self) -> *mut pjsua::pj_pool_t {
         self.pjsua_pool
     }
 
-    pub fn alloc<'a, T: Sized + Default>(&'a self, objects_count: usize) -> PoolBuffer<'a, T> {
+    pub fn stats(&self) -> MemoryPoolStats {
         unsafe {
-            let elem_size = std::mem::size_of::<T>();
+            MemoryPoolStats {
+                used_size: pjsua::pj_pool_get_used_size(self.pjsua_pool) as usize,
+                capacity: pjsua::pj_pool_get_capacity(self.pjsua_pool) as usize,
+            }
+        }
+    }
 
-            let buffer = pjsua::pj_pool_calloc(self.pjsua_pool, objects_count, elem_size) as *mut T;
+    pub fn alloc<T: Default>(&self, objects_count: usize) -> Result<PoolBuffer<'_, T>, PjsuaError> {
+        self.alloc_with(objects_count, |_| T::default())
+    }
 
-            write(buffer, T::default());
+    //f is called with the index of every element.
+    pub fn alloc_with<T>(
+        &self,
+        objects_count: usize,
+        mut f: impl FnMut(usize) -> T,
+    ) -> Result<PoolBuffer<'_, T>, PjsuaError> {
+        let buffer = self.alloc_uninit::<T>(objects_count)?;
+
+        //elements written before a panic in f are leaked, not dropped.
+        for i in 0..objects_count {
+            unsafe { buffer.as_ptr().add(i).write(f(i)) };
+        }
 
-            let pool_buffer =
-                std::slice::from_raw_parts_mut::<'a>(buffer, objects_count * elem_size);
+        let pool_buffer = unsafe { std::slice::from_raw_parts_mut(buffer.as_ptr(), objects_count) };
 
-            PoolBuffer {
-                pool_buffer,
-                objects_count,
-                _mem_pool: self,
-            }
+        Ok(PoolBuffer {
+            pool_buffer,
+            _mem_pool: self,
+        })
+    }
+
+    //pjlib only aligns allocations to PJ_POOL_ALIGNMENT, so the allocation is padded and the
+    //pointer aligned for T here.
+    fn alloc_uninit<T>(&self, objects_count: usize) -> Result<NonNull<T>, PjsuaError> {
+        if size_of::<T>() == 0 || objects_count == 0 {
+            return Ok(NonNull::dangling());
+        }
+
+        let size = size_of::<T>()
+            .checked_mul(objects_count)
+            .and_then(|size| size.checked_add(align_of::<T>() - 1))
+            .ok_or_else(|| {
+                PjsuaError::invalid_config(
+                    "pj_pool_alloc",
+                    format!(
+                        "{} elements of {} bytes overflow usize",
+                        objects_count,
+                        size_of::<T>()
+                    ),
+                )
+            })?;
+
+        let buffer = unsafe { pjsua::pj_pool_alloc(self.pjsua_pool, size) } as *mut u8;
+
+        if buffer.is_null() {
+            return Err(PjsuaError::Lifecycle {
+                operation: "pj_pool_alloc",
+                reason: "memory pool exhausted",
+            });
         }
+
+        let offset = buffer.align_offset(align_of::<T>());
+
+        Ok(unsafe { NonNull::new_unchecked(buffer.add(offset) as *mut T) })
     }
 }
 
 impl Drop for PjsuaMemoryPool {
     fn drop(&mut self) {
-        unsafe {
-            pjsua::pj_pool_release(self.pjsua_pool);
+        let pjsua_pool = self.pjsua_pool;
+
+        let released =
+            unsafe { run_on_pjsua_thread_blocking(|| pjsua::pj_pool_release(pjsua_pool)) };
+
+        if let Err(e) = released {
+            tracing::warn!(error = %e, "failed to release memory pool");
         }
     }
 }
@@ -0,0 +1,235 @@
+use crate::error::{catch_callback_panic, get_error_as_result, PjsuaError};
+use crate::tokio_utils::{run_on_pjsua_thread, run_on_pjsua_thread_blocking};
+
+use std::ffi::CStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::sync::oneshot as tokio_oneshot;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NatType {
+    Unknown,
+    ErrUnknown,
+    Open,
+    Blocked,
+    SymmetricUdp,
+    FullCone,
+    Symmetric,
+    Restricted,
+    PortRestricted,
+}
+
+impl TryFrom<u32> for NatType {
+    type Error = ();
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            pjsua::pj_stun_nat_type_PJ_STUN_NAT_TYPE_UNKNOWN => Ok(NatType::Unknown),
+            pjsua::pj_stun_nat_type_PJ_STUN_NAT_TYPE_ERR_UNKNOWN => Ok(NatType::ErrUnknown),
+            pjsua::pj_stun_nat_type_PJ_STUN_NAT_TYPE_OPEN => Ok(NatType::Open),
+            pjsua::pj_stun_nat_type_PJ_STUN_NAT_TYPE_BLOCKED => Ok(NatType::Blocked),
+            pjsua::pj_stun_nat_type_PJ_STUN_NAT_TYPE_SYMMETRIC_UDP => Ok(NatType::SymmetricUdp),
+            pjsua::pj_stun_nat_type_PJ_STUN_NAT_TYPE_FULL_CONE => Ok(NatType::FullCone),
+            pjsua::pj_stun_nat_type_PJ_STUN_NAT_TYPE_SYMMETRIC => Ok(NatType::Symmetric),
+            pjsua::pj_stun_nat_type_PJ_STUN_NAT_TYPE_RESTRICTED => Ok(NatType::Restricted),
+            pjsua::pj_stun_nat_type_PJ_STUN_NAT_TYPE_PORT_RESTRICTED => Ok(NatType::PortRestricted),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NatDetectResult {
+    pub status: Result<(), PjsuaError>,
+    pub nat_type: NatType,
+    pub nat_type_name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StunUse {
+    Default,
+    Disabled,
+    RetryOnFailure,
+}
+
+impl StunUse {
+    fn as_raw(&self) -> pjsua::pjsua_stun_use {
+        match self {
+            StunUse::Default => pjsua::pjsua_stun_use_PJSUA_STUN_USE_DEFAULT,
+            StunUse::Disabled => pjsua::pjsua_stun_use_PJSUA_STUN_USE_DISABLED,
+            StunUse::RetryOnFailure => pjsua::pjsua_stun_use_PJSUA_STUN_RETRY_ON_FAILURE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactRewriteMethod {
+    //unregister the old Contact before registering the new one.
+    Unregister,
+    //register the new Contact without unregistering the old one.
+    NoUnregister,
+}
+
+//every option left as None keeps the pjsua default.
+#[derive(Debug, Clone, Default)]
+pub struct AccountNatConfig {
+    pub sip_stun_use: Option<StunUse>,
+    pub media_stun_use: Option<StunUse>,
+    pub allow_contact_rewrite: Option<bool>,
+    pub contact_rewrite_method: Option<ContactRewriteMethod>,
+    //also update the Contact of dialogs and subscriptions, not only of the registration.
+    pub contact_rewrite_always_update: Option<bool>,
+    pub allow_via_rewrite: Option<bool>,
+    pub allow_sdp_nat_rewrite: Option<bool>,
+    pub use_rfc5626: Option<bool>,
+    //Duration::ZERO disables keep-alive.
+    pub ka_interval: Option<Duration>,
+}
+
+fn pj_bool(value: bool) -> pjsua::pj_bool_t {
+    match value {
+        true => pjsua::pj_constants__PJ_TRUE as pjsua::pj_bool_t,
+        false => pjsua::pj_constants__PJ_FALSE as pjsua::pj_bool_t,
+    }
+}
+
+impl AccountNatConfig {
+    pub(crate) fn apply(&self, account_config: &mut pjsua::pjsua_acc_config) {
+        if let Some(sip_stun_use) = self.sip_stun_use {
+            account_config.sip_stun_use = sip_stun_use.as_raw();
+        }
+
+        if let Some(media_stun_use) = self.media_stun_use {
+            account_config.media_stun_use = media_stun_use.as_raw();
+        }
+
+        if let Some(allow_contact_rewrite) = self.allow_contact_rewrite {
+            account_config.allow_contact_rewrite = pj_bool(allow_contact_rewrite);
+        }
+
+        if self.contact_rewrite_method.is_some() || self.contact_rewrite_always_update.is_some() {
+            const ALWAYS_UPDATE: i32 =
+                pjsua::pjsua_contact_rewrite_method_PJSUA_CONTACT_REWRITE_ALWAYS_UPDATE as i32;
+            const UNREGISTER: i32 =
+                pjsua::pjsua_contact_rewrite_method_PJSUA_CONTACT_REWRITE_UNREGISTER as i32;
+            const NO_UNREG: i32 =
+                pjsua::pjsua_contact_rewrite_method_PJSUA_CONTACT_REWRITE_NO_UNREG as i32;
+
+            let current = account_config.contact_rewrite_method;
+
+            let method = match self.contact_rewrite_method {
+                Some(ContactRewriteMethod::Unregister) => UNREGISTER,
+                Some(ContactRewriteMethod::NoUnregister) => NO_UNREG,
+                None => current & (UNREGISTER | NO_UNREG),
+            };
+
+            let always_update = match self.contact_rewrite_always_update {
+                Some(true) => ALWAYS_UPDATE,
+                Some(false) => 0,
+                None => current & ALWAYS_UPDATE,
+            };
+
+            account_config.contact_rewrite_method = method | always_update;
+        }
+
+        if let Some(allow_via_rewrite) = self.allow_via_rewrite {
+            account_config.allow_via_rewrite = pj_bool(allow_via_rewrite);
+        }
+
+        if let Some(allow_sdp_nat_rewrite) = self.allow_sdp_nat_rewrite {
+            account_config.allow_sdp_nat_rewrite = pj_bool(allow_sdp_nat_rewrite);
+        }
+
+        if let Some(use_rfc5626) = self.use_rfc5626 {
+            account_config.use_rfc5626 = pj_bool(use_rfc5626);
+        }
+
+        if let Some(ka_interval) = self.ka_interval {
+            account_config.ka_interval = ka_interval.as_secs() as u32;
+        }
+    }
+}
+
+//pjsua reports the NAT detection result through a global callback without user data,
+//therefore every pending detect_nat_type() call waits here for the next result.
+static NAT_DETECT_WAITERS: Mutex<Vec<tokio_oneshot::Sender<NatDetectResult>>> =
+    Mutex::new(Vec::new());
+
+pub(crate) unsafe extern "C" fn on_nat_detect(res: *const pjsua::pj_stun_nat_detect_result) {
+    let _ = catch_callback_panic("on_nat_detect", || {
+        let Some(res) = res.as_ref() else {
+            tracing::warn!("on_nat_detect: result is null");
+            return;
+        };
+
+        let nat_type = NatType::try_from(res.nat_type as u32).unwrap_or(NatType::Unknown);
+
+        let nat_type_name = match res.nat_type_name.is_null() {
+            true => String::new(),
+            false => CStr::from_ptr(res.nat_type_name)
+                .to_string_lossy()
+                .into_owned(),
+        };
+
+        let result = NatDetectResult {
+            status: get_error_as_result("on_nat_detect", res.status),
+            nat_type,
+            nat_type_name,
+        };
+
+        tracing::debug!(?result, "on_nat_detect");
+
+        let waiters = match NAT_DETECT_WAITERS.lock() {
+            Ok(mut waiters) => std::mem::take(&mut *waiters),
+            Err(_) => return,
+        };
+
+        for waiter in waiters {
+            let _ = waiter.send(result.clone());
+        }
+    });
+}
+
+//requires at least one STUN server in PjsuaConfig.
+//The waiter is registered on the worker right before pjsua_detect_nat_type, so a detection
+//that finished earlier, e.g. the one pjsua starts after pjsua_init, can't resolve it.
+pub(crate) async fn detect_nat_type() -> Result<NatDetectResult, PjsuaError> {
+    let (result_tx, result_rx) = tokio_oneshot::channel();
+
+    run_on_pjsua_thread(move || unsafe {
+        match NAT_DETECT_WAITERS.lock() {
+            Ok(mut waiters) => {
+                waiters.retain(|waiter| !waiter.is_closed());
+                waiters.push(result_tx);
+            }
+            Err(_) => {
+                return Err(PjsuaError::Lifecycle {
+                    operation: "detect_nat_type",
+                    reason: "NAT detection waiters are poisoned",
+                })
+            }
+        }
+
+        get_error_as_result("pjsua_detect_nat_type", pjsua::pjsua_detect_nat_type())
+    })
+    .await??;
+
+    result_rx.await.map_err(|_| PjsuaError::ChannelClosed {
+        operation: "detect_nat_type",
+    })
+}
+
+pub(crate) fn current_nat_type() -> Result<NatType, PjsuaError> {
+    let mut nat_type: pjsua::pj_stun_nat_type = 0;
+
+    unsafe {
+        run_on_pjsua_thread_blocking(|| {
+            get_error_as_result(
+                "pjsua_get_nat_type",
+                pjsua::pjsua_get_nat_type(&mut nat_type),
+            )
+        })??;
+    }
+
+    Ok(NatType::try_from(nat_type as u32).unwrap_or(NatType::Unknown))
+}
@@ -1,48 +1,85 @@
 use super::error::PjsuaError;
+use std::cell::Cell;
 use std::marker::PhantomData;
+use std::mem::MaybeUninit;
 use std::sync::Mutex;
+use std::time::Duration;
 
 use super::error::get_error_as_result;
 
-use crate::{pjsua_account_config, pjsua_config, transport};
+use crate::tokio_utils::{self, run_on_pjsua_thread, run_on_pjsua_thread_blocking};
+use crate::{
+    pjsua_account_config, pjsua_config, pjsua_log, pjsua_master_port, pjsua_nat, transport,
+};
+
+//true while a PjsuaInstanceHandle exists, released once pjsua is destroyed.
+static INSTANCE_CRATED: Mutex<bool> = Mutex::new(false);
+
+fn release_instance() {
+    let mut instance_guard = INSTANCE_CRATED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    *instance_guard = false;
+}
+
+//true from pjsua_create until pjsua_destroy2 returned.
+pub(crate) fn instance_created() -> bool {
+    *INSTANCE_CRATED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
 
 pub(crate) struct PjsuaInstanceHandle {
     _not_send_sync: PhantomData<*mut ()>,
+    //pjsua_destroy_flag bits passed to pjsua_destroy2, set by an incomplete shutdown.
+    destroy_flags: Cell<u32>,
     _private: (),
 }
 
 impl Drop for PjsuaInstanceHandle {
     fn drop(&mut self) {
+        let destroy_flags = self.destroy_flags.get();
+
         unsafe {
-            eprintln!("Dropping PjsuaInstanceHandle");
-            let status = get_error_as_result(pjsua::pjsua_destroy());
+            let status = run_on_pjsua_thread_blocking(|| {
+                get_error_as_result("pjsua_destroy2", pjsua::pjsua_destroy2(destroy_flags))
+            })
+            .and_then(|status| status);
 
-            eprintln!("Dropping PjsuaInstanceHandle status: {:?}", status);
+            if let Err(e) = status {
+                tracing::error!(error = ?e, "pjsua_destroy2 failed");
+            }
         }
+
+        release_instance();
     }
 }
 
 impl PjsuaInstanceHandle {
     pub fn get_instance() -> Result<PjsuaInstanceHandle, PjsuaError> {
-        static INSTANCE_CRATED: Mutex<bool> = Mutex::new(false);
-
         if let Ok(mut instance_guard) = INSTANCE_CRATED.try_lock() {
             let val = *instance_guard;
             if let false = val {
                 *instance_guard = true;
-                unsafe {
-                    get_error_as_result(pjsua::pjsua_create())?;
+                let status = unsafe { get_error_as_result("pjsua_create", pjsua::pjsua_create()) };
+
+                if let Err(e) = status {
+                    *instance_guard = false;
+                    return Err(e);
                 }
+
                 return Ok(PjsuaInstanceHandle {
+                    destroy_flags: Cell::new(0),
                     _private: (),
                     _not_send_sync: PhantomData,
                 });
             };
         }
 
-        Err(PjsuaError {
-            code: -1,
-            message: "Pjsua instance already created".to_string(),
+        Err(PjsuaError::Lifecycle {
+            operation: "get_instance",
+            reason: "pjsua instance already created",
         })
     }
 }
@@ -55,6 +92,10 @@ pub struct PjsuaInstanceUninit {
 //The order of fields is important for the drop order.
 //PjsuaInstanceInit MUST be dropped as the last, as it uninitializes pjsua completely.
 pub struct PjsuaInstanceInit {
+    master_port: Option<(
+        *mut pjsua::pjmedia_port,
+        pjsua_master_port::MasterPortFormat,
+    )>,
     log_config: pjsua_config::LogConfig,
     _media_config: pjsua_config::MediaConfig,
     pjsua_config: pjsua_config::PjsuaConfig,
@@ -63,33 +104,54 @@ pub struct PjsuaInstanceInit {
 
 pub struct PjsuaInstanceInitTransportConfigured {
     pjsua_instance_init: PjsuaInstanceInit,
-    transport: transport::PjsuaTransport,
+    transports: Vec<(transport::TransportId, transport::PjsuaTransport)>,
 }
 
 pub struct PjsuaInstanceStarted {
+    master_port: Option<(
+        *mut pjsua::pjmedia_port,
+        pjsua_master_port::MasterPortFormat,
+    )>,
     _log_config: pjsua_config::LogConfig,
     _pjsua_config: pjsua_config::PjsuaConfig,
-    _transport: transport::PjsuaTransport,
+    transports: Vec<(transport::TransportId, transport::PjsuaTransport)>,
     _handle: PjsuaInstanceHandle,
 }
 
 impl PjsuaInstanceInitTransportConfigured {
     pub fn start(self) -> Result<PjsuaInstanceStarted, PjsuaError> {
         unsafe {
-            get_error_as_result(pjsua::pjsua_start())?;
+            get_error_as_result("pjsua_start", pjsua::pjsua_start())?;
         }
 
         let handle = self.pjsua_instance_init.handle;
 
         let instance_started = PjsuaInstanceStarted {
+            master_port: self.pjsua_instance_init.master_port,
             _log_config: self.pjsua_instance_init.log_config,
             _pjsua_config: self.pjsua_instance_init.pjsua_config,
-            _transport: self.transport,
+            transports: self.transports,
             _handle: handle,
         };
 
         Ok(instance_started)
     }
+
+    //adds another transport, e.g. TCP next to UDP or one bound to a different interface.
+    pub fn add_transport(
+        &mut self,
+        mut transport: transport::PjsuaTransport,
+    ) -> Result<transport::TransportId, PjsuaError> {
+        let transport_id = transport.create()?;
+
+        self.transports.push((transport_id, transport));
+
+        Ok(transport_id)
+    }
+
+    pub fn transport_ids(&self) -> Vec<transport::TransportId> {
+        self.transports.iter().map(|(id, _)| *id).collect()
+    }
 }
 
 impl PjsuaInstanceUninit {
@@ -108,6 +170,15 @@ impl PjsuaInstanceUninit {
 
         instance_init
     }
+
+    pub fn init_with(
+        self,
+        pjsua_config: pjsua_config::PjsuaConfig,
+        log_config: pjsua_config::LogConfig,
+        media_config: pjsua_config::MediaConfig,
+    ) -> Result<PjsuaInstanceInit, PjsuaError> {
+        PjsuaInstanceInit::from(self, pjsua_config, log_config, media_config)
+    }
 }
 
 impl PjsuaInstanceInit {
@@ -115,22 +186,14 @@ impl PjsuaInstanceInit {
         self,
         mut transport: transport::PjsuaTransport,
     ) -> Result<PjsuaInstanceInitTransportConfigured, PjsuaError> {
-        unsafe {
-            let mut transport_id: pjsua::pjsua_transport_id = 0;
-
-            get_error_as_result(pjsua::pjsua_transport_create(
-                pjsua::pjsip_transport_type_e_PJSIP_TRANSPORT_UDP,
-                transport.as_mut(),
-                &mut transport_id,
-            ))?;
+        let transport_id = transport.create()?;
 
-            let instance_transport_set = PjsuaInstanceInitTransportConfigured {
-                pjsua_instance_init: self,
-                transport,
-            };
+        let instance_transport_set = PjsuaInstanceInitTransportConfigured {
+            pjsua_instance_init: self,
+            transports: vec![(transport_id, transport)],
+        };
 
-            Ok(instance_transport_set)
-        }
+        Ok(instance_transport_set)
     }
 }
 
@@ -139,11 +202,212 @@ impl PjsuaInstanceStarted {
         &self,
         account: pjsua_account_config::AccountConfig,
     ) -> Result<pjsua_account_config::AccountConfigAdded, PjsuaError> {
-        let account_added = account.add_to_instance_init(&self);
+        account.add_to_instance(self).await
+    }
+
+    pub fn transport_ids(&self) -> Vec<transport::TransportId> {
+        self.transports.iter().map(|(id, _)| *id).collect()
+    }
+
+    //every receiver gets all transport events that happen after it was created.
+    pub fn transport_events(&self) -> transport::TransportEventReceiver {
+        transport::TransportEventReceiver::subscribe()
+    }
+
+    //resolves once pjsua reports the result through on_nat_detect.
+    pub async fn detect_nat_type(&self) -> Result<pjsua_nat::NatDetectResult, PjsuaError> {
+        pjsua_nat::detect_nat_type().await
+    }
+
+    //codec_id may be a prefix like "PCMU" or a full id like "opus/48000/2".
+    //priority 0 disables the codec, 255 makes it the most preferred.
+    pub fn set_codec_priority(&self, codec_id: &str, priority: u8) -> Result<(), PjsuaError> {
+        let codec_id = std::ffi::CString::new(codec_id).map_err(|_| {
+            PjsuaError::invalid_config(
+                "set_codec_priority",
+                "codec_id contains an interior NUL byte",
+            )
+        })?;
+
+        unsafe {
+            run_on_pjsua_thread_blocking(|| {
+                let codec_id = pjsua::pj_str(codec_id.as_ptr() as *mut i8);
+
+                get_error_as_result(
+                    "pjsua_codec_set_priority",
+                    pjsua::pjsua_codec_set_priority(&codec_id, priority),
+                )
+            })?
+        }
+    }
+
+    //only available with SoundDevice::ManualClock, the bridge does not advance on its own then.
+    pub fn master_port(&self) -> Option<pjsua_master_port::ConferenceMasterPort<'_>> {
+        self.master_port
+            .map(|(port, format)| pjsua_master_port::ConferenceMasterPort::new(port, format, self))
+    }
+
+    //changes the pjsip log verbosity of the running instance.
+    pub fn set_log_level(&self, level: pjsua_log::LogLevel) {
+        pjsua_log::set_log_level(level);
+    }
+
+    //returns the result of the last NAT type detection.
+    pub fn nat_type(&self) -> Result<pjsua_nat::NatType, PjsuaError> {
+        pjsua_nat::current_nat_type()
+    }
+
+    //hangs up all calls, unregisters all accounts and waits until the calls are gone and every
+    //un-REGISTER got its response. Takes &self, as accounts and calls borrow the instance:
+    //they stay valid and dropping them afterwards sends nothing. pjsua is destroyed when the
+    //instance is dropped; if shutdown didn't finish in time, without waiting for the network.
+    pub async fn shutdown(&self, timeout: Duration) -> Result<(), ShutdownIncomplete> {
+        const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+        let deadline = std::time::Instant::now() + timeout;
+
+        let mut progress = ShutdownProgress::default();
+
+        let error = match run_on_pjsua_thread(|| unsafe {
+            pjsua::pjsua_call_hangup_all();
+            unregister_accounts();
+        })
+        .await
+        {
+            Ok(()) => loop {
+                match run_on_pjsua_thread(ShutdownProgress::poll).await {
+                    Ok(polled) => progress = polled,
+                    Err(e) => break Some(e),
+                }
+
+                if progress.is_done() {
+                    return Ok(());
+                }
+
+                if std::time::Instant::now() >= deadline {
+                    break None;
+                }
+
+                tokio_utils::sleep(POLL_INTERVAL).await;
+            },
+            Err(e) => Some(e),
+        };
+
+        //whatever is left would only be waited for again by pjsua_destroy2.
+        self._handle
+            .destroy_flags
+            .set(pjsua::pjsua_destroy_flag_PJSUA_DESTROY_NO_NETWORK);
+
+        Err(ShutdownIncomplete {
+            active_calls: progress.active_calls,
+            pending_unregistrations: progress.pending_unregistrations,
+            error,
+        })
+    }
+}
+
+//what was still outstanding when the shutdown timeout elapsed, or when the pjsua worker
+//failed. The counts are from the last successful poll then, zero if there was none.
+#[derive(Debug)]
+pub struct ShutdownIncomplete {
+    pub active_calls: u32,
+    pub pending_unregistrations: u32,
+    pub error: Option<PjsuaError>,
+}
+
+impl std::fmt::Display for ShutdownIncomplete {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "shutdown incomplete: {} active calls, {} pending un-REGISTERs",
+            self.active_calls, self.pending_unregistrations
+        )?;
+
+        match &self.error {
+            Some(error) => write!(f, " ({})", error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for ShutdownIncomplete {}
+
+//pjsua reports this as pjsua_acc_info::expires once the account has no registration session,
+//i.e. it was never registered or its un-REGISTER was answered.
+const PJSIP_EXPIRES_NOT_SPECIFIED: u32 = 0xFFFFFFFF;
+
+#[derive(Default)]
+struct ShutdownProgress {
+    active_calls: u32,
+    pending_unregistrations: u32,
+}
+
+impl ShutdownProgress {
+    fn poll() -> Self {
+        let pending_unregistrations = unsafe { account_ids() }
+            .into_iter()
+            .filter(|account_id| unsafe { is_registration_pending(*account_id) })
+            .count();
+
+        ShutdownProgress {
+            active_calls: unsafe { pjsua::pjsua_call_get_count() },
+            pending_unregistrations: pending_unregistrations as u32,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.active_calls == 0 && self.pending_unregistrations == 0
+    }
+}
+
+unsafe fn account_ids() -> Vec<pjsua::pjsua_acc_id> {
+    let mut account_ids = vec![0 as pjsua::pjsua_acc_id; pjsua::PJSUA_MAX_ACC as usize];
+    let mut count = account_ids.len() as u32;
 
-        //note, this does not use FFI
+    if let Err(e) = get_error_as_result(
+        "pjsua_enum_accs",
+        pjsua::pjsua_enum_accs(account_ids.as_mut_ptr(), &mut count),
+    ) {
+        tracing::warn!(error = ?e, "failed to enumerate accounts");
+        return Vec::new();
+    }
+
+    account_ids.truncate(count as usize);
+    account_ids
+}
 
-        account_added
+//true while the account has a registration session, registered or with a REGISTER in flight.
+unsafe fn is_registration_pending(account_id: pjsua::pjsua_acc_id) -> bool {
+    let mut account_info = MaybeUninit::<pjsua::pjsua_acc_info>::zeroed().assume_init();
+
+    let status = get_error_as_result(
+        "pjsua_acc_get_info",
+        pjsua::pjsua_acc_get_info(account_id, &mut account_info),
+    );
+
+    match status {
+        Ok(()) => account_info.expires as u32 != PJSIP_EXPIRES_NOT_SPECIFIED,
+        Err(e) => {
+            tracing::warn!(acc_id = account_id, error = ?e, "failed to get account info");
+            false
+        }
+    }
+}
+
+unsafe fn unregister_accounts() {
+    for account_id in account_ids() {
+        if !is_registration_pending(account_id) {
+            continue;
+        }
+
+        let status = get_error_as_result(
+            "pjsua_acc_set_registration",
+            pjsua::pjsua_acc_set_registration(account_id, pjsua::pj_constants__PJ_FALSE as i32),
+        );
+
+        if let Err(e) = status {
+            tracing::warn!(acc_id = account_id, error = ?e, "failed to unregister account");
+        }
     }
 }
 
@@ -161,15 +425,47 @@ impl PjsuaInstanceInit {
         mut media_config: pjsua_config::MediaConfig,
     ) -> Result<Self, PjsuaError> {
         unsafe {
-            get_error_as_result(pjsua::pjsua_init(
-                pjsua_config.as_mut(),
-                log_config.as_mut(),
-                media_config.as_mut(),
-            ))?;
+            get_error_as_result(
+                "pjsua_init",
+                pjsua::pjsua_init(
+                    pjsua_config.as_mut(),
+                    log_config.as_mut(),
+                    media_config.as_mut(),
+                ),
+            )?;
+
+            let master_port = match media_config.sound_device() {
+                pjsua_config::SoundDevice::Null => {
+                    get_error_as_result("pjsua_set_null_snd_dev", pjsua::pjsua_set_null_snd_dev())?;
+                    None
+                }
+                pjsua_config::SoundDevice::ManualClock => {
+                    let port = pjsua::pjsua_set_no_snd_dev();
+
+                    if port.is_null() {
+                        return Err(PjsuaError::Lifecycle {
+                            operation: "pjsua_set_no_snd_dev",
+                            reason: "no conference master port returned",
+                        });
+                    }
 
-            get_error_as_result(pjsua::pjsua_set_null_snd_dev())?;
+                    let media_cfg = media_config.as_mut();
+
+                    let format = pjsua_master_port::MasterPortFormat {
+                        clock_rate: media_cfg.clock_rate,
+                        channel_count: media_cfg.channel_count,
+                        samples_per_frame: (media_cfg.clock_rate as u64
+                            * media_cfg.channel_count as u64
+                            * media_cfg.audio_frame_ptime as u64
+                            / 1000) as usize,
+                    };
+
+                    Some((port, format))
+                }
+            };
 
             Ok(PjsuaInstanceInit {
+                master_port,
                 handle: instance.handle,
                 pjsua_config,
                 log_config,
@@ -178,3 +474,217 @@ impl PjsuaInstanceInit {
         }
     }
 }
+
+//the stage of Softphone setup that failed. Indices refer to the order the
+//transports and accounts were given to the builder.
+#[derive(Debug)]
+pub enum SoftphoneError {
+    NoTransport,
+    InvalidAccount(usize, pjsua_account_config::AccountConfigError),
+    Create(PjsuaError),
+    Init(PjsuaError),
+    Transport(usize, PjsuaError),
+    Start(PjsuaError),
+    CodecPriority(String, PjsuaError),
+    AddAccount(usize, PjsuaError),
+}
+
+impl std::fmt::Display for SoftphoneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SoftphoneError::NoTransport => write!(f, "no transport configured"),
+            SoftphoneError::InvalidAccount(index, err) => {
+                write!(f, "invalid account config #{}: {}", index, err)
+            }
+            SoftphoneError::Create(err) => write!(f, "pjsua_create failed: {:?}", err),
+            SoftphoneError::Init(err) => write!(f, "pjsua_init failed: {:?}", err),
+            SoftphoneError::Transport(index, err) => {
+                write!(f, "creating transport #{} failed: {:?}", index, err)
+            }
+            SoftphoneError::Start(err) => write!(f, "pjsua_start failed: {:?}", err),
+            SoftphoneError::CodecPriority(codec_id, err) => {
+                write!(
+                    f,
+                    "setting priority of codec {} failed: {:?}",
+                    codec_id, err
+                )
+            }
+            SoftphoneError::AddAccount(index, err) => {
+                write!(f, "adding account #{} failed: {:?}", index, err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SoftphoneError {}
+
+//runs create, init, transport setup and start in one go.
+//Accounts are validated up front, built once pjsua is started and registered with
+//Softphone::add_accounts.
+pub struct SoftphoneBuilder {
+    pjsua_config: Option<pjsua_config::PjsuaConfig>,
+    log_config: Option<pjsua_config::LogConfig>,
+    media_config: Option<pjsua_config::MediaConfig>,
+    transports: Vec<transport::PjsuaTransport>,
+    accounts: Vec<pjsua_account_config::AccountConfigBuilder>,
+    codec_priorities: Vec<(String, u8)>,
+}
+
+impl SoftphoneBuilder {
+    pub fn pjsua_config(mut self, pjsua_config: pjsua_config::PjsuaConfig) -> Self {
+        self.pjsua_config = Some(pjsua_config);
+        self
+    }
+
+    pub fn log_config(mut self, log_config: pjsua_config::LogConfig) -> Self {
+        self.log_config = Some(log_config);
+        self
+    }
+
+    pub fn media_config(mut self, media_config: pjsua_config::MediaConfig) -> Self {
+        self.media_config = Some(media_config);
+        self
+    }
+
+    //transports are created in the order they are added, the first one gets the first TransportId.
+    pub fn transport(mut self, transport: transport::PjsuaTransport) -> Self {
+        self.transports.push(transport);
+        self
+    }
+
+    pub fn account(mut self, account: pjsua_account_config::AccountConfigBuilder) -> Self {
+        self.accounts.push(account);
+        self
+    }
+
+    //applied in order once pjsua is started, see PjsuaInstanceStarted::set_codec_priority.
+    pub fn codec_priority(mut self, codec_id: impl Into<String>, priority: u8) -> Self {
+        self.codec_priorities.push((codec_id.into(), priority));
+        self
+    }
+
+    pub fn build(self) -> Result<Softphone, SoftphoneError> {
+        if self.transports.is_empty() {
+            return Err(SoftphoneError::NoTransport);
+        }
+
+        //checked before anything is set up, a bad account doesn't bring up pjsua.
+        for (index, account) in self.accounts.iter().enumerate() {
+            account
+                .validate()
+                .map_err(|err| SoftphoneError::InvalidAccount(index, err))?;
+        }
+
+        let instance = PjsuaInstanceUninit::get_instance().map_err(SoftphoneError::Create)?;
+
+        let instance = instance
+            .init_with(
+                self.pjsua_config
+                    .unwrap_or_else(pjsua_config::PjsuaConfig::new),
+                self.log_config.unwrap_or_default(),
+                self.media_config.unwrap_or_default(),
+            )
+            .map_err(SoftphoneError::Init)?;
+
+        let mut transports = self.transports.into_iter().enumerate();
+
+        //checked above
+        let (_, first_transport) = transports.next().unwrap();
+
+        let mut instance = instance
+            .set_transport(first_transport)
+            .map_err(|err| SoftphoneError::Transport(0, err))?;
+
+        for (index, transport) in transports {
+            instance
+                .add_transport(transport)
+                .map_err(|err| SoftphoneError::Transport(index, err))?;
+        }
+
+        let instance = instance.start().map_err(SoftphoneError::Start)?;
+
+        for (codec_id, priority) in self.codec_priorities {
+            instance
+                .set_codec_priority(&codec_id, priority)
+                .map_err(|err| SoftphoneError::CodecPriority(codec_id, err))?;
+        }
+
+        //pjsip_parse_uri and pjsua_acc_config_default need pjsua, so accounts are built last.
+        //After validate, only a URI pjsip parses differently can fail here.
+        let accounts = self
+            .accounts
+            .into_iter()
+            .enumerate()
+            .map(|(index, account)| {
+                account
+                    .build()
+                    .map_err(|err| SoftphoneError::InvalidAccount(index, err))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Softphone {
+            pending_accounts: Mutex::new(accounts),
+            instance,
+        })
+    }
+}
+
+//keep in mind the order of fields, the instance has to be dropped last.
+pub struct Softphone {
+    pending_accounts: Mutex<Vec<pjsua_account_config::AccountConfig>>,
+    instance: PjsuaInstanceStarted,
+}
+
+impl Softphone {
+    pub fn builder() -> SoftphoneBuilder {
+        SoftphoneBuilder {
+            pjsua_config: None,
+            log_config: None,
+            media_config: None,
+            transports: Vec::new(),
+            accounts: Vec::new(),
+            codec_priorities: Vec::new(),
+        }
+    }
+
+    pub fn instance(&self) -> &PjsuaInstanceStarted {
+        &self.instance
+    }
+
+    //registers the accounts given to the builder, in the same order.
+    //Subsequent calls return an empty Vec.
+    pub async fn add_accounts(
+        &self,
+    ) -> Result<Vec<pjsua_account_config::AccountConfigAdded>, SoftphoneError> {
+        let pending_accounts = std::mem::take(&mut *self.pending_accounts.lock().unwrap());
+
+        let mut accounts_added = Vec::with_capacity(pending_accounts.len());
+
+        for (index, account) in pending_accounts.into_iter().enumerate() {
+            let account_added = self
+                .instance
+                .add_account(account)
+                .await
+                .map_err(|err| SoftphoneError::AddAccount(index, err))?;
+
+            accounts_added.push(account_added);
+        }
+
+        Ok(accounts_added)
+    }
+
+    //see PjsuaInstanceStarted::shutdown, accounts that were never added are discarded.
+    pub async fn shutdown(&self, timeout: Duration) -> Result<(), ShutdownIncomplete> {
+        self.pending_accounts.lock().unwrap().clear();
+
+        self.instance.shutdown(timeout).await
+    }
+}
+
+impl std::ops::Deref for Softphone {
+    type Target = PjsuaInstanceStarted;
+
+    fn deref(&self) -> &Self::Target {
+        &self.instance
+    }
+}
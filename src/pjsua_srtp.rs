@@ -0,0 +1,193 @@
+use crate::pj_types::pj_str_to_string;
+
+use std::ffi::CStr;
+use std::mem::MaybeUninit;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SrtpUse {
+    Disabled,
+    Optional,
+    Mandatory,
+}
+
+impl SrtpUse {
+    fn as_raw(&self) -> pjsua::pjmedia_srtp_use {
+        match self {
+            SrtpUse::Disabled => pjsua::pjmedia_srtp_use_PJMEDIA_SRTP_DISABLED,
+            SrtpUse::Optional => pjsua::pjmedia_srtp_use_PJMEDIA_SRTP_OPTIONAL,
+            SrtpUse::Mandatory => pjsua::pjmedia_srtp_use_PJMEDIA_SRTP_MANDATORY,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SrtpSecureSignaling {
+    NotRequired,
+    //SRTP requires TLS on the next hop.
+    Tls,
+    //SRTP requires a SIPS URI, i.e. TLS end-to-end.
+    EndToEndSips,
+}
+
+impl SrtpSecureSignaling {
+    fn as_raw(&self) -> i32 {
+        match self {
+            SrtpSecureSignaling::NotRequired => 0,
+            SrtpSecureSignaling::Tls => 1,
+            SrtpSecureSignaling::EndToEndSips => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SrtpCryptoSuite {
+    AeadAes256Gcm,
+    AeadAes128Gcm,
+    AesCm256HmacSha1_80,
+    AesCm256HmacSha1_32,
+    AesCm128HmacSha1_80,
+    AesCm128HmacSha1_32,
+}
+
+impl SrtpCryptoSuite {
+    fn as_cstr(&self) -> &'static CStr {
+        let name: &'static [u8] = match self {
+            SrtpCryptoSuite::AeadAes256Gcm => b"AEAD_AES_256_GCM\0",
+            SrtpCryptoSuite::AeadAes128Gcm => b"AEAD_AES_128_GCM\0",
+            SrtpCryptoSuite::AesCm256HmacSha1_80 => b"AES_256_CM_HMAC_SHA1_80\0",
+            SrtpCryptoSuite::AesCm256HmacSha1_32 => b"AES_256_CM_HMAC_SHA1_32\0",
+            SrtpCryptoSuite::AesCm128HmacSha1_80 => b"AES_CM_128_HMAC_SHA1_80\0",
+            SrtpCryptoSuite::AesCm128HmacSha1_32 => b"AES_CM_128_HMAC_SHA1_32\0",
+        };
+
+        CStr::from_bytes_with_nul(name).unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SrtpKeying {
+    Sdes,
+    DtlsSrtp,
+}
+
+impl SrtpKeying {
+    fn as_raw(&self) -> pjsua::pjmedia_srtp_keying_method {
+        match self {
+            SrtpKeying::Sdes => pjsua::pjmedia_srtp_keying_method_PJMEDIA_SRTP_KEYING_SDES,
+            SrtpKeying::DtlsSrtp => pjsua::pjmedia_srtp_keying_method_PJMEDIA_SRTP_KEYING_DTLS_SRTP,
+        }
+    }
+}
+
+//empty crypto_suites and keying keep the pjsua defaults (all suites, SDES and DTLS-SRTP).
+#[derive(Debug, Clone)]
+pub struct SrtpConfig {
+    pub use_srtp: SrtpUse,
+    pub secure_signaling: Option<SrtpSecureSignaling>,
+    //ordered by priority.
+    pub crypto_suites: Vec<SrtpCryptoSuite>,
+    //ordered by priority.
+    pub keying: Vec<SrtpKeying>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SrtpConfigError {
+    TooManyCryptoSuites(usize),
+    TooManyKeyingMethods(usize),
+}
+
+impl std::fmt::Display for SrtpConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SrtpConfigError::TooManyCryptoSuites(max) => {
+                write!(f, "too many SRTP crypto suites, at most {} allowed", max)
+            }
+            SrtpConfigError::TooManyKeyingMethods(max) => {
+                write!(f, "too many SRTP keying methods, at most {} allowed", max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SrtpConfigError {}
+
+pub(crate) struct SrtpConfigRaw {
+    pub(crate) use_srtp: pjsua::pjmedia_srtp_use,
+    pub(crate) secure_signaling: Option<i32>,
+    pub(crate) srtp_opt: pjsua::pjsua_srtp_opt,
+}
+
+impl SrtpConfig {
+    //crypto names point to static strings, so the raw options have no lifetime constraints.
+    pub(crate) fn to_raw(&self) -> Result<SrtpConfigRaw, SrtpConfigError> {
+        let mut srtp_opt = unsafe { MaybeUninit::<pjsua::pjsua_srtp_opt>::zeroed().assume_init() };
+
+        unsafe {
+            pjsua::pjsua_srtp_opt_default(&mut srtp_opt);
+        }
+
+        if !self.crypto_suites.is_empty() {
+            let max = srtp_opt.crypto.len();
+
+            if self.crypto_suites.len() > max {
+                return Err(SrtpConfigError::TooManyCryptoSuites(max));
+            }
+
+            for (i, crypto_suite) in self.crypto_suites.iter().enumerate() {
+                let crypto = &mut srtp_opt.crypto[i];
+
+                //empty key makes pjmedia generate a random one.
+                crypto.key = unsafe { std::mem::zeroed() };
+                crypto.name = unsafe { pjsua::pj_str(crypto_suite.as_cstr().as_ptr() as *mut i8) };
+                crypto.flags = 0;
+            }
+
+            srtp_opt.crypto_count = self.crypto_suites.len() as u32;
+        }
+
+        if !self.keying.is_empty() {
+            let max = srtp_opt.keying.len();
+
+            if self.keying.len() > max {
+                return Err(SrtpConfigError::TooManyKeyingMethods(max));
+            }
+
+            for (i, keying) in self.keying.iter().enumerate() {
+                srtp_opt.keying[i] = keying.as_raw();
+            }
+
+            srtp_opt.keying_count = self.keying.len() as u32;
+        }
+
+        Ok(SrtpConfigRaw {
+            use_srtp: self.use_srtp.as_raw(),
+            secure_signaling: self.secure_signaling.map(|s| s.as_raw()),
+            srtp_opt,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaEncryption {
+    pub active: bool,
+    pub tx_crypto_suite: String,
+    pub rx_crypto_suite: String,
+}
+
+//returns None if no media of the call uses an SRTP transport.
+pub(crate) fn call_media_encryption(call_id: pjsua::pjsua_call_id) -> Option<MediaEncryption> {
+    let call_info = crate::pjsua_call::get_call_info(call_id).ok()?;
+
+    (0..call_info.media_cnt).find_map(|media_index| unsafe {
+        crate::pjsua_call::with_transport_specific_info(
+            call_id,
+            media_index,
+            pjsua::pjmedia_transport_type_PJMEDIA_TRANSPORT_TYPE_SRTP,
+            |srtp_info: &pjsua::pjmedia_srtp_info| MediaEncryption {
+                active: srtp_info.active != 0,
+                tx_crypto_suite: pj_str_to_string(&srtp_info.tx_policy.name),
+                rx_crypto_suite: pj_str_to_string(&srtp_info.rx_policy.name),
+            },
+        )
+    })
+}
@@ -1,12 +1,16 @@
-use super::error::ffi_assert_res;
-use super::error::get_error_as_result;
-use std::cell::RefCell;
+use super::error::{catch_callback_panic, get_error_as_result, PjsuaError};
+use std::cell::{Cell, RefCell};
+use std::collections::BinaryHeap;
 use std::ffi::CString;
+use std::future::Future;
 use std::mem::MaybeUninit;
+use std::pin::Pin;
 use std::ptr;
 use std::sync::atomic::AtomicUsize;
-use tokio::task::spawn_blocking as tokio_spawn_blocking;
-use tokio::task::JoinHandle;
+use std::sync::mpsc as std_mpsc;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot as tokio_oneshot;
 
 struct PjsuaThreadMeta {
     _thread_name: CString,
@@ -17,7 +21,7 @@ struct PjsuaThreadMeta {
 }
 
 impl PjsuaThreadMeta {
-    fn register() -> PjsuaThreadMeta {
+    fn register() -> Result<PjsuaThreadMeta, PjsuaError> {
         static THREAD_NAME_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
         let counter = THREAD_NAME_COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
@@ -42,41 +46,314 @@ impl PjsuaThreadMeta {
             }
         };
 
-        let status = get_error_as_result(status);
+        get_error_as_result("pj_thread_register", status)?;
 
-        ffi_assert_res(status);
-
-        PjsuaThreadMeta {
+        Ok(PjsuaThreadMeta {
             _thread_name: thread_name,
             _descriptor: descriptor,
             _pj_thread_handle: handle,
             _not_send_sync: std::marker::PhantomData,
+        })
+    }
+}
+
+//registers the calling thread with pjlib, again if pjsua was re-created since.
+//The registration stays valid until the thread exits.
+pub(crate) fn register_current_thread() -> Result<(), PjsuaError> {
+    thread_local! {
+        static THREAD_META: RefCell<Option<PjsuaThreadMeta>> = RefCell::new(None);
+    }
+
+    THREAD_META.with(|thread_meta_opt| {
+        let mut thread_meta_opt = thread_meta_opt.borrow_mut();
+
+        let registered = unsafe { pjsua::pj_thread_is_registered() != 0 };
+
+        if thread_meta_opt.is_none() || !registered {
+            *thread_meta_opt = Some(PjsuaThreadMeta::register()?);
         }
+
+        Ok(())
+    })
+}
+
+//commands get the result of registering the worker thread and report a failure to their
+//caller instead of running.
+type PjsuaCommand = Box<dyn FnOnce(Result<(), PjsuaError>) + Send>;
+
+const WORKER_COMMAND: &str = "pjsua worker command";
+
+thread_local! {
+    static IS_PJSUA_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+//single thread owning the pjsua calls that change state: calls, accounts, transports, codecs
+//and conference bridge ports, from async and sync APIs alike. Commands run in the order they
+//were sent, so these are serialised. Callbacks run on pjsua's own threads, the master port
+//on the thread driving it.
+struct PjsuaWorker {
+    commands_tx: std_mpsc::Sender<PjsuaCommand>,
+}
+
+impl PjsuaWorker {
+    fn get() -> &'static PjsuaWorker {
+        static PJSUA_WORKER: OnceLock<PjsuaWorker> = OnceLock::new();
+
+        PJSUA_WORKER.get_or_init(|| {
+            let (commands_tx, commands_rx) = std_mpsc::channel::<PjsuaCommand>();
+
+            std::thread::Builder::new()
+                .name("pjsua_worker".to_string())
+                .spawn(move || {
+                    IS_PJSUA_WORKER.with(|is_pjsua_worker| is_pjsua_worker.set(true));
+
+                    //commands catch panics of their closure, see worker_command.
+                    for command in commands_rx {
+                        command(register_current_thread());
+                    }
+                })
+                .expect("failed to spawn the pjsua worker thread");
+
+            PjsuaWorker { commands_tx }
+        })
+    }
+
+    fn send(&self, command: PjsuaCommand) -> Result<(), PjsuaError> {
+        self.commands_tx
+            .send(command)
+            .map_err(|_| PjsuaError::ChannelClosed {
+                operation: WORKER_COMMAND,
+            })
     }
 }
 
-//spawn_pjsua wont work! task may be scheduled on a different thread and the initalization is not
-//performed!
-//this however wont resume the task on the same thread, but it will be scheduled on the same thread
-pub(crate) fn spawn_blocking_pjsua<F, R>(f: F) -> JoinHandle<R>
+//runs f unless the worker thread isn't registered with pjlib, a panic in f becomes
+//PjsuaError::CallbackPanicked.
+fn worker_command<F, R>(registered: Result<(), PjsuaError>, f: F) -> Result<R, PjsuaError>
+where
+    F: FnOnce() -> R,
+{
+    registered.and_then(|_| catch_callback_panic(WORKER_COMMAND, f))
+}
+
+//the reply is only dropped without an answer if the worker thread is gone.
+fn reply_dropped() -> PjsuaError {
+    PjsuaError::ChannelClosed {
+        operation: WORKER_COMMAND,
+    }
+}
+
+//runs f on the pjsua worker thread and resolves with its result.
+pub(crate) async fn run_on_pjsua_thread<F, R>(f: F) -> Result<R, PjsuaError>
 where
     F: FnOnce() -> R + Send + 'static,
     R: Send + 'static,
 {
-    let f_wrapped = move || {
-        thread_local! {
-            static THREAD_META: RefCell<Option<PjsuaThreadMeta>> = RefCell::new(None);
+    let (reply_tx, reply_rx) = tokio_oneshot::channel();
+
+    let command: PjsuaCommand = Box::new(move |registered| {
+        let _ = reply_tx.send(worker_command(registered, f));
+    });
+
+    PjsuaWorker::get().send(command)?;
+
+    reply_rx.await.map_err(|_| reply_dropped())?
+}
+
+//runs f on the pjsua worker thread and blocks the calling thread until f returned. Used by
+//sync APIs and Drop impls, async APIs use run_on_pjsua_thread. Runs f directly if called on
+//the worker itself, so helpers built on it can be called from worker commands.
+//Safety: f and its result cross to the worker thread without being Send. They must not
+//contain thread-affine values like Rc or lock guards, raw pjsua pointers are fine.
+pub(crate) unsafe fn run_on_pjsua_thread_blocking<'f, F, R>(f: F) -> Result<R, PjsuaError>
+where
+    F: FnOnce() -> R + 'f,
+    R: 'f,
+{
+    if IS_PJSUA_WORKER.with(Cell::get) {
+        return worker_command(Ok(()), f);
+    }
+
+    let (reply_tx, reply_rx) = std_mpsc::sync_channel(1);
+
+    let command: Box<dyn FnOnce(Result<(), PjsuaError>) + 'f> = Box::new(move |registered| {
+        let _ = reply_tx.send(worker_command(registered, f));
+    });
+
+    //the calling thread is blocked until the command ran or was dropped, so neither the
+    //borrows of f nor the missing Send bound are observable.
+    let command =
+        std::mem::transmute::<Box<dyn FnOnce(Result<(), PjsuaError>) + 'f>, PjsuaCommand>(command);
+
+    PjsuaWorker::get().send(command)?;
+
+    reply_rx.recv().map_err(|_| reply_dropped())?
+}
+
+//moves a value that is not Send, like a config or media port holding raw pjsua pointers, into
+//a run_on_pjsua_thread command and back.
+pub(crate) struct AssertSend<T>(T);
+
+impl<T> AssertSend<T> {
+    //Safety: as for run_on_pjsua_thread_blocking, value must not contain thread-affine values.
+    pub(crate) unsafe fn new(value: T) -> Self {
+        AssertSend(value)
+    }
+
+    //closures capturing .0 would capture the field alone, without the Send impl.
+    pub(crate) fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+unsafe impl<T> Send for AssertSend<T> {}
+
+pub type BoxedFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+//the executor services the crate needs besides the pjsua worker thread.
+//Channels are tokio::sync, which do not depend on the tokio runtime.
+pub trait AsyncRuntime: Send + Sync {
+    fn sleep(&self, duration: Duration) -> BoxedFuture;
+}
+
+#[cfg(feature = "tokio-runtime")]
+pub struct TokioRuntime;
+
+#[cfg(feature = "tokio-runtime")]
+impl AsyncRuntime for TokioRuntime {
+    fn sleep(&self, duration: Duration) -> BoxedFuture {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+//works on any executor and without one, all sleeps share a single timer thread.
+pub struct ThreadRuntime;
+
+impl AsyncRuntime for ThreadRuntime {
+    fn sleep(&self, duration: Duration) -> BoxedFuture {
+        //a deadline beyond what Instant can represent is never reached.
+        let Some(deadline) = Instant::now().checked_add(duration) else {
+            return Box::pin(std::future::pending());
+        };
+
+        let (wake_tx, wake_rx) = tokio_oneshot::channel();
+
+        TimerThread::get().add(deadline, wake_tx);
+
+        Box::pin(async move {
+            let _ = wake_rx.await;
+        })
+    }
+}
+
+struct Timer {
+    deadline: Instant,
+    wake_tx: tokio_oneshot::Sender<()>,
+}
+
+//ordered by deadline only, reversed so that BinaryHeap pops the earliest deadline first.
+impl Ord for Timer {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        other.deadline.cmp(&self.deadline)
+    }
+}
+
+impl PartialOrd for Timer {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Timer {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl Eq for Timer {}
+
+struct TimerThread {
+    timers_tx: std_mpsc::Sender<Timer>,
+}
+
+impl TimerThread {
+    fn get() -> &'static TimerThread {
+        static TIMER_THREAD: OnceLock<TimerThread> = OnceLock::new();
+
+        TIMER_THREAD.get_or_init(|| {
+            let (timers_tx, timers_rx) = std_mpsc::channel::<Timer>();
+
+            std::thread::Builder::new()
+                .name("pjsua_timer".to_string())
+                .spawn(move || Self::run(timers_rx))
+                .expect("failed to spawn the timer thread");
+
+            TimerThread { timers_tx }
+        })
+    }
+
+    fn add(&self, deadline: Instant, wake_tx: tokio_oneshot::Sender<()>) {
+        //the thread only exits with the process, a lost timer would hang the sleep forever.
+        self.timers_tx
+            .send(Timer { deadline, wake_tx })
+            .expect("timer thread exited");
+    }
+
+    //waits for new timers until the earliest deadline, then wakes every sleep that is due.
+    //Sleeps that were dropped in the meantime are skipped.
+    fn run(timers_rx: std_mpsc::Receiver<Timer>) {
+        let mut timers = BinaryHeap::<Timer>::new();
+
+        loop {
+            let received = match timers.peek() {
+                Some(timer) => {
+                    let timeout = timer.deadline.saturating_duration_since(Instant::now());
+                    timers_rx.recv_timeout(timeout)
+                }
+                None => timers_rx
+                    .recv()
+                    .map_err(|_| std_mpsc::RecvTimeoutError::Disconnected),
+            };
+
+            match received {
+                Ok(timer) => timers.push(timer),
+                Err(std_mpsc::RecvTimeoutError::Timeout) => {}
+                Err(std_mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+
+            let now = Instant::now();
+
+            while timers.peek().is_some_and(|timer| timer.deadline <= now) {
+                if let Some(timer) = timers.pop() {
+                    let _ = timer.wake_tx.send(());
+                }
+            }
         }
+    }
+}
+
+static CUSTOM_RUNTIME: OnceLock<Box<dyn AsyncRuntime>> = OnceLock::new();
+
+//installs the runtime used for all timers, can be set once per process.
+//Returns the runtime back if one was already set.
+pub fn set_runtime(runtime: Box<dyn AsyncRuntime>) -> Result<(), Box<dyn AsyncRuntime>> {
+    CUSTOM_RUNTIME.set(runtime)
+}
+
+//the custom runtime if set, otherwise tokio when called from within a tokio runtime.
+fn runtime() -> &'static dyn AsyncRuntime {
+    if let Some(runtime) = CUSTOM_RUNTIME.get() {
+        return runtime.as_ref();
+    }
+
+    #[cfg(feature = "tokio-runtime")]
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return &TokioRuntime;
+    }
+
+    &ThreadRuntime
+}
 
-        THREAD_META.with(|thread_meta_opt| {
-            *thread_meta_opt.borrow_mut() = Some(PjsuaThreadMeta::register());
-            eprintln!(
-                "spawn_blocking_pjsua: running on thread {:?}",
-                std::thread::current().id()
-            );
-        });
-        f()
-    };
-
-    tokio_spawn_blocking(f_wrapped)
+pub(crate) async fn sleep(duration: Duration) {
+    runtime().sleep(duration).await
 }
@@ -1,12 +1,399 @@
+use crate::error::{catch_callback_panic, get_error_as_result, PjsuaError};
+use crate::pj_types::pj_str_to_string;
+use crate::pjsua_config::PjsuaConfigError;
+use crate::tokio_utils::run_on_pjsua_thread_blocking;
+
+use std::ffi::{CStr, CString};
 use std::mem::MaybeUninit;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportType {
+    Udp,
+    Tcp,
+    Tls,
+    Udp6,
+    Tcp6,
+    Tls6,
+}
+
+impl TransportType {
+    fn as_raw(&self) -> pjsua::pjsip_transport_type_e {
+        match self {
+            TransportType::Udp => pjsua::pjsip_transport_type_e_PJSIP_TRANSPORT_UDP,
+            TransportType::Tcp => pjsua::pjsip_transport_type_e_PJSIP_TRANSPORT_TCP,
+            TransportType::Tls => pjsua::pjsip_transport_type_e_PJSIP_TRANSPORT_TLS,
+            TransportType::Udp6 => pjsua::pjsip_transport_type_e_PJSIP_TRANSPORT_UDP6,
+            TransportType::Tcp6 => pjsua::pjsip_transport_type_e_PJSIP_TRANSPORT_TCP6,
+            TransportType::Tls6 => pjsua::pjsip_transport_type_e_PJSIP_TRANSPORT_TLS6,
+        }
+    }
+
+    pub fn is_ipv6(&self) -> bool {
+        matches!(
+            self,
+            TransportType::Udp6 | TransportType::Tcp6 | TransportType::Tls6
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsMethod {
+    //let the SSL backend negotiate the highest supported version.
+    #[default]
+    Default,
+    TlsV1,
+    TlsV1_1,
+    TlsV1_2,
+    TlsV1_3,
+}
+
+impl TlsMethod {
+    fn as_raw(&self) -> pjsua::pjsip_ssl_method {
+        match self {
+            TlsMethod::Default => pjsua::pjsip_ssl_method_PJSIP_SSL_UNSPECIFIED_METHOD,
+            TlsMethod::TlsV1 => pjsua::pjsip_ssl_method_PJSIP_TLSV1_METHOD,
+            TlsMethod::TlsV1_1 => pjsua::pjsip_ssl_method_PJSIP_TLSV1_1_METHOD,
+            TlsMethod::TlsV1_2 => pjsua::pjsip_ssl_method_PJSIP_TLSV1_2_METHOD,
+            TlsMethod::TlsV1_3 => pjsua::pjsip_ssl_method_PJSIP_TLSV1_3_METHOD,
+        }
+    }
+}
+
+//all files are PEM encoded and read by the SSL backend when the transport is created.
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+    pub ca_list_file: Option<PathBuf>,
+    pub cert_file: Option<PathBuf>,
+    pub privkey_file: Option<PathBuf>,
+    pub privkey_password: Option<String>,
+    pub method: TlsMethod,
+    //OpenSSL cipher names, e.g. "ECDHE-RSA-AES256-GCM-SHA384". Empty keeps the backend default.
+    pub ciphers: Vec<String>,
+    pub verify_server: bool,
+    pub verify_client: bool,
+    pub require_client_cert: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlsConfigError {
+    FileNotFound(PathBuf),
+    InvalidPath(PathBuf),
+    InteriorNul(&'static str),
+}
+
+impl std::fmt::Display for TlsConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TlsConfigError::FileNotFound(path) => write!(f, "file not found: {:?}", path),
+            TlsConfigError::InvalidPath(path) => write!(f, "path is not valid UTF-8: {:?}", path),
+            TlsConfigError::InteriorNul(field) => {
+                write!(f, "field contains an interior NUL byte: {}", field)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TlsConfigError {}
+
+fn path_cstring(path: &Path) -> Result<CString, TlsConfigError> {
+    if !path.is_file() {
+        return Err(TlsConfigError::FileNotFound(path.to_path_buf()));
+    }
+
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| TlsConfigError::InvalidPath(path.to_path_buf()))?;
+
+    CString::new(path_str).map_err(|_| TlsConfigError::InvalidPath(path.to_path_buf()))
+}
+
+//owns everything the raw pjsip_tls_setting points to.
+struct TlsSettingRaw {
+    _strings_owned: Vec<CString>,
+    _ciphers: Vec<pjsua::pj_ssl_cipher>,
+}
+
+impl TlsConfig {
+    fn validate(&self) -> Result<(), TlsConfigError> {
+        for path in [&self.ca_list_file, &self.cert_file, &self.privkey_file]
+            .into_iter()
+            .flatten()
+        {
+            path_cstring(path)?;
+        }
+
+        if let Some(password) = &self.privkey_password {
+            CString::new(password.as_str())
+                .map_err(|_| TlsConfigError::InteriorNul("privkey_password"))?;
+        }
+
+        Ok(())
+    }
+
+    fn apply(
+        &self,
+        tls_setting: &mut pjsua::pjsip_tls_setting,
+    ) -> Result<TlsSettingRaw, PjsuaError> {
+        let mut strings_owned = Vec::new();
+
+        let mut own = |string: CString| {
+            let pj_string = unsafe { pjsua::pj_str(string.as_ptr() as *mut i8) };
+            strings_owned.push(string);
+            pj_string
+        };
+
+        let to_pjsua_error =
+            |e: TlsConfigError| PjsuaError::invalid_config("tls_config", e.to_string());
+
+        if let Some(ca_list_file) = &self.ca_list_file {
+            tls_setting.ca_list_file = own(path_cstring(ca_list_file).map_err(to_pjsua_error)?);
+        }
+
+        if let Some(cert_file) = &self.cert_file {
+            tls_setting.cert_file = own(path_cstring(cert_file).map_err(to_pjsua_error)?);
+        }
+
+        if let Some(privkey_file) = &self.privkey_file {
+            tls_setting.privkey_file = own(path_cstring(privkey_file).map_err(to_pjsua_error)?);
+        }
+
+        if let Some(password) = &self.privkey_password {
+            let password = CString::new(password.as_str())
+                .map_err(|_| to_pjsua_error(TlsConfigError::InteriorNul("privkey_password")))?;
+            tls_setting.password = own(password);
+        }
+
+        //cipher ids are only known once the SSL backend is initialized.
+        let ciphers = self
+            .ciphers
+            .iter()
+            .map(|name| {
+                let cipher_name = CString::new(name.as_str())
+                    .map_err(|_| to_pjsua_error(TlsConfigError::InteriorNul("ciphers")))?;
+
+                match unsafe { pjsua::pj_ssl_cipher_id(cipher_name.as_ptr()) } {
+                    pjsua::pj_ssl_cipher_PJ_TLS_UNKNOWN_CIPHER => Err(PjsuaError::invalid_config(
+                        "tls_config",
+                        format!("unknown TLS cipher: {}", name),
+                    )),
+                    cipher => Ok(cipher),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        tls_setting.method = self.method.as_raw();
+        tls_setting.verify_server = self.verify_server as pjsua::pj_bool_t;
+        tls_setting.verify_client = self.verify_client as pjsua::pj_bool_t;
+        tls_setting.require_client_cert = self.require_client_cert as pjsua::pj_bool_t;
+
+        let mut ciphers = ciphers;
+
+        if !ciphers.is_empty() {
+            tls_setting.ciphers_num = ciphers.len() as u32;
+            tls_setting.ciphers = ciphers.as_mut_ptr();
+        }
+
+        Ok(TlsSettingRaw {
+            _strings_owned: strings_owned,
+            _ciphers: ciphers,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QosType {
+    BestEffort,
+    Background,
+    Video,
+    Voice,
+    Control,
+    Signalling,
+}
+
+impl QosType {
+    fn as_raw(&self) -> pjsua::pj_qos_type {
+        match self {
+            QosType::BestEffort => pjsua::pj_qos_type_PJ_QOS_TYPE_BEST_EFFORT,
+            QosType::Background => pjsua::pj_qos_type_PJ_QOS_TYPE_BACKGROUND,
+            QosType::Video => pjsua::pj_qos_type_PJ_QOS_TYPE_VIDEO,
+            QosType::Voice => pjsua::pj_qos_type_PJ_QOS_TYPE_VOICE,
+            QosType::Control => pjsua::pj_qos_type_PJ_QOS_TYPE_CONTROL,
+            QosType::Signalling => pjsua::pj_qos_type_PJ_QOS_TYPE_SIGNALLING,
+        }
+    }
+}
+
+//qos_type selects a predefined traffic class, dscp and so_priority override it.
+#[derive(Debug, Clone, Default)]
+pub struct QosConfig {
+    pub qos_type: Option<QosType>,
+    //6 bit DSCP value, e.g. 46 (EF) for RTP or 24 (CS3) for SIP.
+    pub dscp: Option<u8>,
+    pub so_priority: Option<u8>,
+}
+
+impl QosConfig {
+    fn apply(&self, transport_config: &mut pjsua::pjsua_transport_config) {
+        if let Some(qos_type) = self.qos_type {
+            transport_config.qos_type = qos_type.as_raw();
+        }
+
+        let qos_params = &mut transport_config.qos_params;
+
+        if let Some(dscp) = self.dscp {
+            qos_params.flags |= pjsua::pj_qos_flag_PJ_QOS_PARAM_HAS_DSCP as u8;
+            qos_params.dscp_val = dscp & 0x3f;
+        }
+
+        if let Some(so_priority) = self.so_priority {
+            qos_params.flags |= pjsua::pj_qos_flag_PJ_QOS_PARAM_HAS_SO_PRIO as u8;
+            qos_params.so_prio = so_priority;
+        }
+    }
+}
+
+//RTP/RTCP sockets of an account. RTP uses even ports from start_port, RTCP the next odd one.
+#[derive(Debug, Clone)]
+pub struct RtpConfig {
+    pub start_port: u16,
+    //number of ports after start_port pjsua may use, None lets pjsua choose.
+    pub port_range: Option<u16>,
+    pub bound_addr: Option<IpAddr>,
+    //address advertised in SDP instead of the bound address.
+    pub public_addr: Option<String>,
+    pub qos: QosConfig,
+}
+
+impl Default for RtpConfig {
+    fn default() -> Self {
+        RtpConfig {
+            start_port: 4000,
+            port_range: None,
+            bound_addr: None,
+            public_addr: None,
+            qos: QosConfig {
+                qos_type: Some(QosType::Voice),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl RtpConfig {
+    //the returned config points into the returned CStrings, which must outlive it.
+    pub(crate) fn to_raw(
+        &self,
+    ) -> Result<(pjsua::pjsua_transport_config, Vec<CString>), PjsuaConfigError> {
+        if let Some(port_range) = self.port_range {
+            if self.start_port as u32 + port_range as u32 > u16::MAX as u32 {
+                return Err(PjsuaConfigError::InvalidValue(
+                    "rtp port_range",
+                    format!("{} + {} exceeds 65535", self.start_port, port_range),
+                ));
+            }
+        }
+
+        let mut transport_config =
+            unsafe { MaybeUninit::<pjsua::pjsua_transport_config>::zeroed().assume_init() };
+
+        unsafe {
+            pjsua::pjsua_transport_config_default(&mut transport_config);
+        }
+
+        transport_config.port = self.start_port as u32;
+
+        if let Some(port_range) = self.port_range {
+            transport_config.port_range = port_range as u32;
+        }
+
+        let mut strings_owned = Vec::new();
+
+        if let Some(bound_addr) = self.bound_addr {
+            let bound_addr = CString::new(bound_addr.to_string()).unwrap();
+            transport_config.bound_addr = unsafe { pjsua::pj_str(bound_addr.as_ptr() as *mut i8) };
+            strings_owned.push(bound_addr);
+        }
+
+        if let Some(public_addr) = &self.public_addr {
+            let public_addr = CString::new(public_addr.as_str())
+                .map_err(|_| PjsuaConfigError::InteriorNul("rtp public_addr"))?;
+            transport_config.public_addr =
+                unsafe { pjsua::pj_str(public_addr.as_ptr() as *mut i8) };
+            strings_owned.push(public_addr);
+        }
+
+        self.qos.apply(&mut transport_config);
+
+        Ok((transport_config, strings_owned))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TransportId(pjsua::pjsua_transport_id);
+
+impl TransportId {
+    pub(crate) fn as_raw(&self) -> pjsua::pjsua_transport_id {
+        self.0
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct PjsuaTransport {
     transport_config: pjsua::pjsua_transport_config,
+    transport_type: TransportType,
+    tls_config: Option<TlsConfig>,
+    bound_addr: Option<IpAddr>,
+    public_addr: Option<String>,
 }
 
 impl PjsuaTransport {
     pub fn new(port: Option<u16>) -> Self {
+        Self::with_type(TransportType::Udp, port)
+    }
+
+    pub fn tcp(port: Option<u16>) -> Self {
+        Self::with_type(TransportType::Tcp, port)
+    }
+
+    //files referenced by tls_config must exist.
+    pub fn tls(port: Option<u16>, tls_config: TlsConfig) -> Result<Self, TlsConfigError> {
+        Self::with_tls(TransportType::Tls, port, tls_config)
+    }
+
+    //IPv6 transports only serve IPv6 peers. For dual-stack, add one transport per address
+    //family, pjsua then picks the transport matching the destination address.
+    pub fn udp6(port: Option<u16>) -> Self {
+        Self::with_type(TransportType::Udp6, port)
+    }
+
+    pub fn tcp6(port: Option<u16>) -> Self {
+        Self::with_type(TransportType::Tcp6, port)
+    }
+
+    pub fn tls6(port: Option<u16>, tls_config: TlsConfig) -> Result<Self, TlsConfigError> {
+        Self::with_tls(TransportType::Tls6, port, tls_config)
+    }
+
+    fn with_tls(
+        transport_type: TransportType,
+        port: Option<u16>,
+        tls_config: TlsConfig,
+    ) -> Result<Self, TlsConfigError> {
+        tls_config.validate()?;
+
+        let mut transport = Self::with_type(transport_type, port);
+        transport.tls_config = Some(tls_config);
+
+        Ok(transport)
+    }
+
+    fn with_type(transport_type: TransportType, port: Option<u16>) -> Self {
         let mut transport_config =
             unsafe { MaybeUninit::<pjsua::pjsua_transport_config>::zeroed().assume_init() };
 
@@ -16,7 +403,104 @@ impl PjsuaTransport {
 
         transport_config.port = port.unwrap_or(0) as u32;
 
-        PjsuaTransport { transport_config }
+        PjsuaTransport {
+            transport_config,
+            transport_type,
+            tls_config: None,
+            bound_addr: None,
+            public_addr: None,
+        }
+    }
+
+    //binds the transport to the given interface instead of all interfaces.
+    //the address family must match the transport type, e.g. ::1 for TransportType::Udp6.
+    pub fn set_bound_addr(&mut self, bound_addr: IpAddr) {
+        self.bound_addr = Some(bound_addr);
+    }
+
+    //QoS/DSCP marking of the SIP signalling sent over this transport.
+    pub fn set_qos(&mut self, qos: &QosConfig) {
+        qos.apply(&mut self.transport_config);
+    }
+
+    //host name or address advertised in Contact and Via instead of the bound address.
+    pub fn set_public_addr(&mut self, public_addr: &str) -> Result<(), PjsuaError> {
+        if public_addr.contains('\0') {
+            return Err(PjsuaError::invalid_config(
+                "set_public_addr",
+                "public_addr contains an interior NUL byte",
+            ));
+        }
+
+        self.public_addr = Some(public_addr.to_string());
+
+        Ok(())
+    }
+
+    pub fn transport_type(&self) -> TransportType {
+        self.transport_type
+    }
+
+    //pjsua_transport_create copies the config, the strings only have to outlive the call.
+    pub(crate) fn create(&mut self) -> Result<TransportId, PjsuaError> {
+        if let Some(bound_addr) = self.bound_addr {
+            if bound_addr.is_ipv6() != self.transport_type.is_ipv6() {
+                return Err(PjsuaError::invalid_config(
+                    "pjsua_transport_create",
+                    format!(
+                        "bound_addr {} does not match transport type {:?}",
+                        bound_addr, self.transport_type
+                    ),
+                ));
+            }
+        }
+
+        let mut transport_config = self.transport_config;
+
+        let bound_addr = self
+            .bound_addr
+            .map(|bound_addr| CString::new(bound_addr.to_string()).unwrap());
+
+        if let Some(bound_addr) = &bound_addr {
+            transport_config.bound_addr = unsafe { pjsua::pj_str(bound_addr.as_ptr() as *mut i8) };
+        }
+
+        let public_addr = self
+            .public_addr
+            .as_deref()
+            .map(|public_addr| CString::new(public_addr).unwrap());
+
+        if let Some(public_addr) = &public_addr {
+            transport_config.public_addr =
+                unsafe { pjsua::pj_str(public_addr.as_ptr() as *mut i8) };
+        }
+
+        let mut transport_id: pjsua::pjsua_transport_id = 0;
+
+        let transport_type = self.transport_type.as_raw();
+
+        let tls_config = self.tls_config.as_ref();
+
+        unsafe {
+            run_on_pjsua_thread_blocking(|| {
+                //pj_ssl_cipher_id in apply needs pjlib as well.
+                let _tls_setting_raw = match tls_config {
+                    Some(tls_config) => Some(tls_config.apply(&mut transport_config.tls_setting)?),
+                    None => None,
+                };
+
+                get_error_as_result(
+                    "pjsua_transport_create",
+                    pjsua::pjsua_transport_create(
+                        transport_type,
+                        &transport_config,
+                        &mut transport_id,
+                    ),
+                )
+            })??;
+        }
+
+        Ok(TransportId(transport_id))
     }
 }
 
@@ -25,3 +509,163 @@ impl AsMut<pjsua::pjsua_transport_config> for PjsuaTransport {
         &mut self.transport_config
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportEventKind {
+    Connected,
+    Disconnected,
+    //the peer certificate failed verification, verify_status is a pj_ssl_cert_verify_flag_t mask.
+    TlsVerificationFailed {
+        verify_status: u32,
+        reasons: Vec<String>,
+    },
+    Shutdown,
+    Destroyed,
+}
+
+#[derive(Debug, Clone)]
+pub struct TransportEvent {
+    pub kind: TransportEventKind,
+    //e.g. "TCP", "TLS6"
+    pub transport_type: String,
+    //"host:port"
+    pub remote_addr: String,
+    pub status: Result<(), PjsuaError>,
+}
+
+//pjsua reports transport state through a global callback without user data, therefore
+//events are broadcast to every TransportEventReceiver.
+fn transport_events_tx() -> &'static tokio::sync::broadcast::Sender<TransportEvent> {
+    static TRANSPORT_EVENTS_TX: OnceLock<tokio::sync::broadcast::Sender<TransportEvent>> =
+        OnceLock::new();
+
+    TRANSPORT_EVENTS_TX.get_or_init(|| tokio::sync::broadcast::channel(64).0)
+}
+
+pub struct TransportEventReceiver {
+    events_rx: tokio::sync::broadcast::Receiver<TransportEvent>,
+}
+
+impl TransportEventReceiver {
+    pub(crate) fn subscribe() -> Self {
+        TransportEventReceiver {
+            events_rx: transport_events_tx().subscribe(),
+        }
+    }
+
+    //events that were not received in time are skipped.
+    pub async fn next_event(&mut self) -> Option<TransportEvent> {
+        use tokio::sync::broadcast::error::RecvError;
+
+        loop {
+            match self.events_rx.recv().await {
+                Ok(event) => return Some(event),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "transport event receiver lagged");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+unsafe fn tls_verification_failure(
+    transport: &pjsua::pjsip_transport,
+    info: &pjsua::pjsip_transport_state_info,
+) -> Option<TransportEventKind> {
+    let flags = transport.flag;
+
+    if flags & pjsua::pjsip_transport_flags_e_PJSIP_TRANSPORT_SECURE == 0 || info.ext_info.is_null()
+    {
+        return None;
+    }
+
+    let tls_info = (info.ext_info as *const pjsua::pjsip_tls_state_info).as_ref()?;
+    let ssl_sock_info = tls_info.ssl_sock_info.as_ref()?;
+
+    let verify_status = ssl_sock_info.verify_status;
+
+    if verify_status == 0 {
+        return None;
+    }
+
+    let mut error_strings = [ptr::null::<std::os::raw::c_char>(); 32];
+    let mut count = error_strings.len() as u32;
+
+    pjsua::pj_ssl_cert_get_verify_status_strings(
+        verify_status,
+        error_strings.as_mut_ptr(),
+        &mut count,
+    );
+
+    let reasons = error_strings
+        .iter()
+        .take(count as usize)
+        .filter(|reason| !reason.is_null())
+        .map(|&reason| CStr::from_ptr(reason).to_string_lossy().into_owned())
+        .collect();
+
+    Some(TransportEventKind::TlsVerificationFailed {
+        verify_status,
+        reasons,
+    })
+}
+
+pub(crate) unsafe extern "C" fn on_transport_state(
+    transport: *mut pjsua::pjsip_transport,
+    state: pjsua::pjsip_transport_state,
+    info: *const pjsua::pjsip_transport_state_info,
+) {
+    let _ = catch_callback_panic("on_transport_state", || {
+        let (Some(transport), Some(info)) = (transport.as_ref(), info.as_ref()) else {
+            return;
+        };
+
+        let kind = match tls_verification_failure(transport, info) {
+            Some(kind) => kind,
+            None => match state {
+                pjsua::pjsip_transport_state_PJSIP_TP_STATE_CONNECTED => {
+                    TransportEventKind::Connected
+                }
+                pjsua::pjsip_transport_state_PJSIP_TP_STATE_DISCONNECTED => {
+                    TransportEventKind::Disconnected
+                }
+                pjsua::pjsip_transport_state_PJSIP_TP_STATE_SHUTDOWN => {
+                    TransportEventKind::Shutdown
+                }
+                pjsua::pjsip_transport_state_PJSIP_TP_STATE_DESTROY => {
+                    TransportEventKind::Destroyed
+                }
+                state => {
+                    tracing::warn!(state, "unknown transport state");
+                    return;
+                }
+            },
+        };
+
+        let transport_type = match transport.type_name.is_null() {
+            true => String::new(),
+            false => CStr::from_ptr(transport.type_name)
+                .to_string_lossy()
+                .into_owned(),
+        };
+
+        let remote_addr = format!(
+            "{}:{}",
+            pj_str_to_string(&transport.remote_name.host),
+            transport.remote_name.port
+        );
+
+        let event = TransportEvent {
+            kind,
+            transport_type,
+            remote_addr,
+            status: get_error_as_result("on_transport_state", info.status),
+        };
+
+        tracing::debug!(?event, "on_transport_state");
+
+        //no receivers is not an error.
+        let _ = transport_events_tx().send(event);
+    });
+}
@@ -0,0 +1,72 @@
+//AccountConfigAdded::modify on an account added to an instance on loopback. The registrar
+//address is a port nobody listens on, modify doesn't depend on the registration.
+
+use pjsip_client::pjsua_account_config::{AccountConfig, AccountConfigError};
+use pjsip_client::pjsua_config::PjsuaConfig;
+use pjsip_client::pjsua_softphone_api::PjsuaInstanceUninit;
+use pjsip_client::transport::PjsuaTransport;
+
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn modify_replaces_proxies() {
+    let registrar_port = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let mut transport = PjsuaTransport::new(None);
+    transport.set_bound_addr(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+    let instance = PjsuaInstanceUninit::get_instance()
+        .unwrap()
+        .init(PjsuaConfig::new())
+        .unwrap()
+        .set_transport(transport)
+        .unwrap()
+        .start()
+        .unwrap();
+
+    let account = AccountConfig::builder()
+        .user("alice")
+        .host("127.0.0.1")
+        .port(registrar_port)
+        .build()
+        .unwrap();
+
+    let mut account = instance.add_account(account).await.unwrap();
+
+    account
+        .modify(|cfg| cfg.set_proxies(&["sip:127.0.0.1:5070;lr", "sip:127.0.0.1:5080;lr"]))
+        .unwrap();
+    assert_eq!(account.as_mut().proxy_cnt, 2);
+
+    //setting the field again replaces the proxies instead of appending to them.
+    account
+        .modify(|cfg| cfg.set_proxies(&["sip:127.0.0.1:5090;lr"]))
+        .unwrap();
+    assert_eq!(account.as_mut().proxy_cnt, 1);
+
+    let error = account
+        .modify(|cfg| cfg.set_proxies(&["sip:127.0.0.1:5070;lr", "not a uri"]))
+        .unwrap_err();
+    assert!(
+        matches!(error, AccountConfigError::InvalidUri("proxy", _)),
+        "{:?}",
+        error
+    );
+
+    let too_many = vec!["sip:127.0.0.1:5070;lr"; 64];
+    let error = account
+        .modify(|cfg| cfg.set_proxies(&too_many))
+        .unwrap_err();
+    assert!(
+        matches!(error, AccountConfigError::TooManyEntries("proxy", _)),
+        "{:?}",
+        error
+    );
+
+    //a failed modify leaves the account unchanged.
+    assert_eq!(account.as_mut().proxy_cnt, 1);
+}
@@ -0,0 +1,53 @@
+//a softphone built from a TOML config with an account, registering with a registrar address on
+//loopback that nobody listens on.
+
+use pjsip_client::pjsua_config_file::SoftphoneFileConfig;
+
+use std::net::{Ipv4Addr, UdpSocket};
+
+const PASSWORD: &str = "correct horse battery staple";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn config_with_account_builds_and_hides_password() {
+    let registrar_port = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let config = format!(
+        r#"
+[ua]
+user_agent = "pjsip-client config test"
+
+[[transports]]
+type = "udp"
+bound_addr = "127.0.0.1"
+
+[[accounts]]
+display_name = "Alice"
+user = "alice"
+password = "{}"
+host = "127.0.0.1"
+port = {}
+"#,
+        PASSWORD, registrar_port
+    );
+
+    let config = SoftphoneFileConfig::from_toml_str(&config).unwrap();
+
+    let config_debug = format!("{:?}", config);
+    assert!(!config_debug.contains(PASSWORD), "{}", config_debug);
+    assert!(
+        config_debug.contains("Value(<redacted>)"),
+        "{}",
+        config_debug
+    );
+
+    let softphone = config.build().unwrap();
+
+    let accounts = softphone.add_accounts().await.unwrap();
+    assert_eq!(accounts.len(), 1);
+
+    drop(accounts);
+}
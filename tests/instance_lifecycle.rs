@@ -0,0 +1,39 @@
+//pjsua can be created again once the previous instance was destroyed.
+//Everything runs in a single test, the instance is process wide.
+
+use pjsip_client::pjsua_config::PjsuaConfig;
+use pjsip_client::pjsua_softphone_api::PjsuaInstanceUninit;
+use pjsip_client::transport::PjsuaTransport;
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+async fn start_and_destroy(round: usize) {
+    let mut transport = PjsuaTransport::new(None);
+    transport.set_bound_addr(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+    let instance = PjsuaInstanceUninit::get_instance()
+        .unwrap_or_else(|e| panic!("round {}: get_instance failed: {:?}", round, e))
+        .init(PjsuaConfig::new())
+        .unwrap()
+        .set_transport(transport)
+        .unwrap()
+        .start()
+        .unwrap();
+
+    assert!(
+        PjsuaInstanceUninit::get_instance().is_err(),
+        "round {}: a second instance was created while the first one is running",
+        round
+    );
+
+    instance.shutdown(Duration::from_secs(5)).await.unwrap();
+
+    drop(instance);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn instance_can_be_created_again_after_destroy() {
+    start_and_destroy(0).await;
+    start_and_destroy(1).await;
+}
@@ -0,0 +1,67 @@
+//dual-stack instance on loopback: UDP on 127.0.0.1 and UDP6 on ::1.
+//A registrar stand-in on ::1 checks that the account's REGISTER is sent through the IPv6
+//transport, which pjsua picks by the address family of the destination.
+
+use pjsip_client::pjsua_account_config::{AccountConfig, Ipv6Use};
+use pjsip_client::pjsua_config::PjsuaConfig;
+use pjsip_client::pjsua_softphone_api::PjsuaInstanceUninit;
+use pjsip_client::transport::PjsuaTransport;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn register_is_sent_over_ipv6_loopback() {
+    let registrar = UdpSocket::bind((Ipv6Addr::LOCALHOST, 0)).unwrap();
+    registrar
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let registrar_port = registrar.local_addr().unwrap().port();
+
+    let mut udp = PjsuaTransport::new(None);
+    udp.set_bound_addr(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+    let mut udp6 = PjsuaTransport::udp6(None);
+    udp6.set_bound_addr(IpAddr::V6(Ipv6Addr::LOCALHOST));
+
+    let mut instance = PjsuaInstanceUninit::get_instance()
+        .unwrap()
+        .init(PjsuaConfig::new())
+        .unwrap()
+        .set_transport(udp)
+        .unwrap();
+
+    instance.add_transport(udp6).unwrap();
+
+    let instance = instance.start().unwrap();
+
+    let account = AccountConfig::builder()
+        .user("alice")
+        .host("::1")
+        .registrar_uri(format!("sip:[::1]:{}", registrar_port))
+        .ipv6_sip_use(Ipv6Use::PreferIpv6)
+        .ipv6_media_use(Ipv6Use::PreferIpv6)
+        .build()
+        .unwrap();
+
+    let _account = instance.add_account(account).await.unwrap();
+
+    let (request, source) = tokio::task::spawn_blocking(move || {
+        let mut buffer = [0u8; 4096];
+        let (len, source) = registrar
+            .recv_from(&mut buffer)
+            .expect("no REGISTER received on ::1");
+
+        (String::from_utf8_lossy(&buffer[..len]).into_owned(), source)
+    })
+    .await
+    .unwrap();
+
+    assert!(request.starts_with("REGISTER "), "{}", request);
+    assert!(
+        request.contains("SIP/2.0/UDP [::1]"),
+        "Via is not the IPv6 transport: {}",
+        request
+    );
+    assert!(matches!(source, SocketAddr::V6(source) if *source.ip() == Ipv6Addr::LOCALHOST));
+}
@@ -0,0 +1,83 @@
+//conference bridge driven by the caller through the master port of SoundDevice::ManualClock.
+//Nothing else ticks the bridge, so every frame is the result of a get_frame call and the
+//bridge runs as fast as it is ticked.
+
+use pjsip_client::pjsua_config::{LogConfig, MediaConfig, PjsuaConfig, SoundDevice};
+use pjsip_client::pjsua_master_port::MasterPortFormat;
+use pjsip_client::pjsua_softphone_api::PjsuaInstanceUninit;
+use pjsip_client::transport::PjsuaTransport;
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+const CLOCK_RATE: u32 = 8000;
+const PTIME_MS: u32 = 20;
+//one minute of audio
+const TICKS: usize = 3000;
+
+#[test]
+fn manual_clock_runs_faster_than_real_time() {
+    let media_config = MediaConfig::builder()
+        .clock_rate(CLOCK_RATE)
+        .channel_count(1)
+        .audio_frame_ptime(PTIME_MS)
+        .sound_device(SoundDevice::ManualClock)
+        .build()
+        .unwrap();
+
+    let mut transport = PjsuaTransport::new(None);
+    transport.set_bound_addr(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+    let instance = PjsuaInstanceUninit::get_instance()
+        .unwrap()
+        .init_with(PjsuaConfig::new(), LogConfig::default(), media_config)
+        .unwrap()
+        .set_transport(transport)
+        .unwrap()
+        .start()
+        .unwrap();
+
+    let master_port = instance
+        .master_port()
+        .expect("no master port with SoundDevice::ManualClock");
+
+    let samples_per_frame = (CLOCK_RATE * PTIME_MS / 1000) as usize;
+
+    assert_eq!(
+        master_port.format(),
+        MasterPortFormat {
+            clock_rate: CLOCK_RATE,
+            channel_count: 1,
+            samples_per_frame,
+        }
+    );
+
+    assert!(master_port.get_frame(&mut [0; 7]).is_err());
+
+    let input: Vec<i16> = (0..samples_per_frame).map(|i| i as i16).collect();
+    let mut output = vec![i16::MAX; samples_per_frame];
+
+    let started = Instant::now();
+
+    for _ in 0..TICKS {
+        master_port.put_frame(&input).unwrap();
+
+        //no port is connected to slot 0, so the bridge has nothing to mix for it.
+        let has_audio = master_port.get_frame(&mut output).unwrap();
+
+        if !has_audio {
+            assert!(output.iter().all(|sample| *sample == 0));
+        }
+    }
+
+    let elapsed = started.elapsed();
+    let audio_time = Duration::from_millis(PTIME_MS as u64 * TICKS as u64);
+
+    //generous on purpose, a real-time clock would need all of audio_time.
+    assert!(
+        elapsed < audio_time,
+        "{:?} of audio took {:?}",
+        audio_time,
+        elapsed
+    );
+}
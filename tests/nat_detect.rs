@@ -0,0 +1,155 @@
+//NAT type detection against a STUN server stand-in on loopback.
+//The stand-in answers plain binding requests and ignores CHANGE-REQUEST ones, like a server
+//without a second address would, so pjnath always gets a final result without network access.
+
+use pjsip_client::pjsua_config::PjsuaConfig;
+use pjsip_client::pjsua_softphone_api::PjsuaInstanceUninit;
+use pjsip_client::transport::PjsuaTransport;
+
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const STUN_BINDING_REQUEST: u16 = 0x0001;
+const STUN_BINDING_RESPONSE: u16 = 0x0101;
+const STUN_MAGIC_COOKIE: u32 = 0x2112_A442;
+
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_CHANGE_REQUEST: u16 = 0x0003;
+const ATTR_CHANGED_ADDRESS: u16 = 0x0005;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+
+struct StunStandIn {
+    primary_addr: SocketAddr,
+    binding_requests: Arc<AtomicUsize>,
+}
+
+impl StunStandIn {
+    fn spawn() -> StunStandIn {
+        let primary = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let changed = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+
+        let primary_addr = primary.local_addr().unwrap();
+        let changed_addr = changed.local_addr().unwrap();
+
+        let binding_requests = Arc::new(AtomicUsize::new(0));
+
+        for socket in [primary, changed] {
+            let binding_requests = binding_requests.clone();
+
+            std::thread::spawn(move || serve(socket, changed_addr, &binding_requests));
+        }
+
+        StunStandIn {
+            primary_addr,
+            binding_requests,
+        }
+    }
+}
+
+fn serve(socket: UdpSocket, changed_addr: SocketAddr, binding_requests: &AtomicUsize) {
+    let mut buffer = [0u8; 1500];
+
+    while let Ok((len, source)) = socket.recv_from(&mut buffer) {
+        let request = &buffer[..len];
+
+        if len < 20 || u16::from_be_bytes([request[0], request[1]]) != STUN_BINDING_REQUEST {
+            continue;
+        }
+
+        binding_requests.fetch_add(1, Ordering::SeqCst);
+
+        if requests_change(request) {
+            continue;
+        }
+
+        let SocketAddr::V4(source) = source else {
+            continue;
+        };
+        let SocketAddr::V4(changed_addr) = changed_addr else {
+            continue;
+        };
+
+        let mut attributes = Vec::new();
+        push_address(&mut attributes, ATTR_MAPPED_ADDRESS, source, false);
+        push_address(&mut attributes, ATTR_XOR_MAPPED_ADDRESS, source, true);
+        push_address(&mut attributes, ATTR_CHANGED_ADDRESS, changed_addr, false);
+
+        let mut response = Vec::with_capacity(20 + attributes.len());
+        response.extend_from_slice(&STUN_BINDING_RESPONSE.to_be_bytes());
+        response.extend_from_slice(&(attributes.len() as u16).to_be_bytes());
+        //magic cookie and transaction id are echoed, this also serves RFC 3489 clients.
+        response.extend_from_slice(&request[4..20]);
+        response.extend_from_slice(&attributes);
+
+        let _ = socket.send_to(&response, source);
+    }
+}
+
+fn requests_change(request: &[u8]) -> bool {
+    let mut attributes = &request[20..];
+
+    while attributes.len() >= 4 {
+        let attr_type = u16::from_be_bytes([attributes[0], attributes[1]]);
+        let attr_len = u16::from_be_bytes([attributes[2], attributes[3]]) as usize;
+        let padded_len = (attr_len + 3) & !3;
+
+        if attributes.len() < 4 + attr_len {
+            return false;
+        }
+
+        if attr_type == ATTR_CHANGE_REQUEST && attr_len == 4 {
+            let flags = u32::from_be_bytes(attributes[4..8].try_into().unwrap());
+            return flags != 0;
+        }
+
+        attributes = &attributes[(4 + padded_len).min(attributes.len())..];
+    }
+
+    false
+}
+
+fn push_address(attributes: &mut Vec<u8>, attr_type: u16, addr: SocketAddrV4, xor: bool) {
+    let (port, ip) = match xor {
+        true => (
+            addr.port() ^ (STUN_MAGIC_COOKIE >> 16) as u16,
+            u32::from(*addr.ip()) ^ STUN_MAGIC_COOKIE,
+        ),
+        false => (addr.port(), u32::from(*addr.ip())),
+    };
+
+    attributes.extend_from_slice(&attr_type.to_be_bytes());
+    attributes.extend_from_slice(&8u16.to_be_bytes());
+    attributes.extend_from_slice(&[0, 0x01]);
+    attributes.extend_from_slice(&port.to_be_bytes());
+    attributes.extend_from_slice(&ip.to_be_bytes());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn detect_nat_type_against_local_stun_stand_in() {
+    let stun = StunStandIn::spawn();
+
+    let mut pjsua_config = PjsuaConfig::new();
+    pjsua_config
+        .set_stun_servers(&[&stun.primary_addr.to_string()])
+        .unwrap();
+
+    let instance = PjsuaInstanceUninit::get_instance()
+        .unwrap()
+        .init(pjsua_config)
+        .unwrap()
+        .set_transport(PjsuaTransport::new(None))
+        .unwrap()
+        .start()
+        .unwrap();
+
+    //pjnath retransmits unanswered CHANGE-REQUEST tests for up to ~10s each.
+    let result = tokio::time::timeout(Duration::from_secs(60), instance.detect_nat_type())
+        .await
+        .expect("NAT detection did not finish")
+        .expect("NAT detection failed to start");
+
+    assert!(result.status.is_ok(), "NAT detection failed: {:?}", result);
+    assert!(stun.binding_requests.load(Ordering::SeqCst) > 0);
+}
@@ -0,0 +1,93 @@
+//TLS transport on loopback with the self-signed certificate in tests/certs.
+//The account registers with the instance's own TLS listener, so the REGISTER opens a TLS
+//connection from the instance to itself. pjsua doesn't accept the REGISTER, but the handshake,
+//including verification of the server certificate, happens before that.
+//Regenerate the certificate with:
+//openssl req -x509 -newkey rsa:2048 -nodes -keyout loopback.key -out loopback.crt -days 36500 \
+//    -subj "/CN=127.0.0.1" -addext "subjectAltName=IP:127.0.0.1,IP:::1,DNS:localhost"
+
+use pjsip_client::pjsua_account_config::{AccountConfig, UriTransportParam};
+use pjsip_client::pjsua_config::PjsuaConfig;
+use pjsip_client::pjsua_softphone_api::PjsuaInstanceUninit;
+use pjsip_client::transport::{PjsuaTransport, TlsConfig, TlsMethod, TransportEventKind};
+
+use std::net::{IpAddr, Ipv4Addr, TcpListener};
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn cert_path(file_name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/certs")
+        .join(file_name)
+}
+
+fn free_tcp_port() -> u16 {
+    TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tls_transport_connects_on_loopback_with_self_signed_cert() {
+    let port = free_tcp_port();
+
+    let tls_config = TlsConfig {
+        ca_list_file: Some(cert_path("loopback.crt")),
+        cert_file: Some(cert_path("loopback.crt")),
+        privkey_file: Some(cert_path("loopback.key")),
+        method: TlsMethod::TlsV1_2,
+        verify_server: true,
+        ..TlsConfig::default()
+    };
+
+    let mut transport = PjsuaTransport::tls(Some(port), tls_config).unwrap();
+    transport.set_bound_addr(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+    let instance = PjsuaInstanceUninit::get_instance()
+        .unwrap()
+        .init(PjsuaConfig::new())
+        .unwrap()
+        .set_transport(transport)
+        .unwrap()
+        .start()
+        .unwrap();
+
+    let mut transport_events = instance.transport_events();
+
+    let account = AccountConfig::builder()
+        .user("alice")
+        .host("127.0.0.1")
+        .port(port)
+        .transport(UriTransportParam::Tls)
+        .registrar_uri(format!("sip:127.0.0.1:{};transport=tls", port))
+        .build()
+        .unwrap();
+
+    let _account = instance.add_account(account).await.unwrap();
+
+    let event = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let event = transport_events
+                .next_event()
+                .await
+                .expect("transport event channel closed");
+
+            match &event.kind {
+                TransportEventKind::Connected
+                | TransportEventKind::TlsVerificationFailed { .. }
+                    if event.transport_type.starts_with("TLS") =>
+                {
+                    return event
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+    .expect("no TLS connection was reported");
+
+    assert_eq!(event.kind, TransportEventKind::Connected, "{:?}", event);
+    assert!(event.status.is_ok(), "{:?}", event);
+}